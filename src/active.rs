@@ -0,0 +1,25 @@
+use std::sync::{Arc, RwLock};
+
+/// An object loaded at runtime that can be replaced while searches are still
+/// using the old one. Readers take an `Arc` snapshot, so a replaced object is
+/// dropped once the last of them is done with it.
+pub struct Active<T>(RwLock<Option<Arc<T>>>);
+
+impl<T> Active<T> {
+    pub const fn new() -> Self {
+        Active(RwLock::new(None))
+    }
+
+    pub fn with(value: T) -> Self {
+        Active(RwLock::new(Some(Arc::new(value))))
+    }
+
+    /// The object last passed to `set`, if any.
+    pub fn get(&self) -> Option<Arc<T>> {
+        self.0.read().unwrap().clone()
+    }
+
+    pub fn set(&self, value: T) {
+        *self.0.write().unwrap() = Some(Arc::new(value));
+    }
+}
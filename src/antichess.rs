@@ -0,0 +1,203 @@
+// this is a submodule of gen_moves so that it can have access to
+// MoveGenerator internals
+use super::eval::*;
+use super::*;
+
+impl MoveGenerator {
+    fn pawn_pushes(&self) -> u64 {
+        let pawns = self.board.pawns() & self.cur_occ;
+
+        if self.board.black {
+            pawns >> 8 & !self.board.occ()
+        } else {
+            pawns << 8 & !self.board.occ()
+        }
+    }
+
+    fn pawn_attacks(&self) -> u64 {
+        let pawns = self.board.pawns() & self.cur_occ;
+
+        if self.board.black {
+            b_pawn_threats(pawns)
+        } else {
+            w_pawn_threats(pawns)
+        }
+    }
+
+    fn piece_attacks(&self) -> u64 {
+        let occ = self.board.occ();
+        let mut out = 0;
+
+        for sq in LocStack(self.board.knights() & self.cur_occ) {
+            out |= TABLES.knight[sq];
+        }
+
+        for sq in LocStack(self.board.kings() & self.cur_occ) {
+            out |= TABLES.king[sq];
+        }
+
+        for sq in LocStack((self.board.bishops() | self.board.queens()) & self.cur_occ) {
+            out |= gen_bishop_moves(sq, occ);
+        }
+
+        for sq in LocStack((self.board.rooks() | self.board.queens()) & self.cur_occ) {
+            out |= gen_rook_moves(sq, occ);
+        }
+
+        out
+    }
+
+    /// Whether the side to move has a capture available, and so is forced to
+    /// make one.
+    pub fn has_antichess_captures(&self) -> bool {
+        let pawn_attacks = self.pawn_attacks();
+
+        (pawn_attacks | self.piece_attacks()) & self.opp_occ != 0
+            || pawn_attacks & self.board.takeable_empties() != 0
+    }
+
+    pub(super) fn has_antichess_moves(&self) -> bool {
+        self.pawn_pushes() != 0
+            || self.piece_attacks() & !self.cur_occ != 0
+            || self.has_antichess_captures()
+    }
+
+    /// Generates pseudo-legal antichess moves, keeping only captures when
+    /// there are any. With `tactical`, quiet moves are never generated.
+    pub(super) fn gen_antichess_moves(&mut self, tactical: bool) {
+        let captures = self.has_antichess_captures();
+
+        if tactical && !captures {
+            self.moves.clear();
+            return;
+        }
+
+        let targets = if captures {
+            self.opp_occ
+        } else {
+            !self.cur_occ
+        };
+
+        self.gen_pseudo_legal(targets, !captures, true);
+    }
+
+    pub(super) fn eval_antichess(&mut self, board: Board, params: &EvalParams) -> i32 {
+        let occ = board.occ();
+        let mut out = board.eval_material(params);
+
+        out += (board.kings() & board.white()).count_ones() as i32 * params.king_weight;
+        out -= (board.kings() & board.black()).count_ones() as i32 * params.king_weight;
+
+        for (black, mul) in [(true, -1), (false, 1)] {
+            self.set_board(Board { black, ..board });
+
+            // running out of pieces or moves wins
+            if board.black == black && !self.has_antichess_moves() {
+                return CHECKMATE;
+            }
+
+            let mobility = |pieces: u64, moves: &dyn Fn(usize) -> u64| {
+                LocStack(pieces)
+                    .map(|sq| (moves(sq) & !self.cur_occ).count_ones() as i32)
+                    .sum::<i32>()
+            };
+
+            let knights = mobility(self.board.knights() & self.cur_occ, &|sq| TABLES.knight[sq]);
+            let bishops = mobility(self.board.bishops() & self.cur_occ, &|sq| {
+                gen_bishop_moves(sq, occ)
+            });
+            let rooks = mobility(self.board.rooks() & self.cur_occ, &|sq| {
+                gen_rook_moves(sq, occ)
+            });
+            let queens = mobility(self.board.queens() & self.cur_occ, &|sq| {
+                gen_bishop_moves(sq, occ) | gen_rook_moves(sq, occ)
+            });
+            let kings = mobility(self.board.kings() & self.cur_occ, &|sq| TABLES.king[sq]);
+
+            out += mul * knights * params.knight_move_weight;
+            out += mul * bishops * params.bishop_move_weight;
+            out += mul * rooks * params.rook_move_weight;
+            out += mul * queens * params.queen_move_weight;
+            out += mul * kings * params.king_move_weight;
+        }
+
+        invert_if(board.black, out.taper(board.phase()))
+    }
+}
+
+#[test]
+fn t_antichess_perft() {
+    // https://www.chessprogramming.org/Perft_Results (antichess)
+    let board = Board::from_fen(START_FEN).with_variant(Variant::Antichess);
+
+    let perft = |board: Board, depth| {
+        fn inner(board: Board, depth: usize) -> usize {
+            let mut generator = MoveGenerator::new(board);
+            generator.gen_moves();
+
+            if depth == 1 {
+                generator.moves.len()
+            } else {
+                generator
+                    .moves
+                    .into_iter()
+                    .map(|b| inner(b, depth - 1))
+                    .sum()
+            }
+        }
+
+        inner(board, depth)
+    };
+
+    assert_eq!(perft(board.clone(), 1), 20);
+    assert_eq!(perft(board.clone(), 2), 400);
+    assert_eq!(perft(board, 3), 8067);
+}
+
+#[test]
+fn t_antichess_moves() {
+    // the pawn capture is compulsory
+    let board = Board::from_fen("4k3/8/8/3p4/4P3/8/8/4K3 w - -").with_variant(Variant::Antichess);
+    let mut generator = MoveGenerator::new(board.clone());
+    generator.gen_moves();
+
+    assert_eq!(
+        generator.moves,
+        vec![board.do_move("e4d5".parse().unwrap())]
+    );
+
+    // kings can be captured and promoted to
+    let board = Board::from_fen("8/4P3/8/8/8/8/8/3kK3 w - -").with_variant(Variant::Antichess);
+    let mut generator = MoveGenerator::new(board.clone());
+    generator.gen_moves();
+
+    assert_eq!(
+        generator.moves,
+        vec![board.do_move("e1d1".parse().unwrap())]
+    );
+
+    let board = Board::from_fen("8/4P3/8/8/8/8/8/k7 w - -").with_variant(Variant::Antichess);
+    let mut generator = MoveGenerator::new(board.clone());
+    generator.gen_moves();
+
+    assert_eq!(generator.moves.len(), 5);
+    assert!(generator
+        .moves
+        .contains(&board.do_move("e7e8k".parse().unwrap())));
+
+    // no castling
+    let board =
+        Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq -").with_variant(Variant::Antichess);
+
+    assert_eq!(board.to_fen(false), "r3k2r/8/8/8/8/8/8/R3K2R w - -");
+
+    // a side without pieces has won
+    let board = Board::from_fen("8/8/8/8/8/8/8/k7 w - -").with_variant(Variant::Antichess);
+    let mut generator = MoveGenerator::new(board.clone());
+
+    assert!(!generator.has_moves());
+    assert_eq!(
+        generator.eval(board, &mut PawnTable::with_len(0)),
+        CHECKMATE
+    );
+}
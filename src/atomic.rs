@@ -0,0 +1,214 @@
+// this is a submodule of gen_moves so that it can have access to
+// MoveGenerator internals
+use super::*;
+
+impl Board {
+    /// Whether the king of `black` could be captured, which is never the case
+    /// while it touches the enemy king.
+    fn is_atomic_check(&self, black: bool) -> bool {
+        let (cur_occ, opp_occ) = if black {
+            (self.black(), self.white())
+        } else {
+            (self.white(), self.black())
+        };
+
+        let kingloc = (self.kings() & cur_occ).trailing_zeros() as usize;
+
+        TABLES.king[kingloc] & self.kings() & opp_occ == 0
+            && self.get_att_def(self.occ(), kingloc) & opp_occ != 0
+    }
+
+    /// Whether `next`, reached from `self`, keeps the mover's king on the
+    /// board and out of check. Exploding the enemy king wins outright.
+    fn is_atomic_legal(&self, next: &Board) -> bool {
+        let (cur_occ, opp_occ) = if self.black {
+            (next.black(), next.white())
+        } else {
+            (next.white(), next.black())
+        };
+
+        if next.kings() & cur_occ == 0 {
+            false
+        } else if next.kings() & opp_occ == 0 {
+            true
+        } else {
+            !next.is_atomic_check(self.black)
+        }
+    }
+}
+
+impl MoveGenerator {
+    pub(super) fn has_atomic_moves(&mut self) -> bool {
+        let moves = std::mem::take(&mut self.moves);
+
+        self.gen_atomic_moves(false);
+
+        let out = !self.moves.is_empty();
+        self.moves = moves;
+        out
+    }
+
+    pub(super) fn gen_atomic_moves(&mut self, tactical: bool) {
+        let targets = if tactical {
+            self.opp_occ
+        } else {
+            !self.cur_occ
+        };
+
+        self.gen_pseudo_legal(targets, !tactical, false);
+
+        let kingloc = (self.board.kings() & self.cur_occ).trailing_zeros() as usize;
+        let opp_king = (self.board.kings() & self.opp_occ).trailing_zeros() as usize;
+
+        // ========== Castles ==========
+        let home_rank = if self.board.black { 7 } else { 0 };
+
+        if !tactical && self.checks == 0 && kingloc / 8 == home_rank {
+            let occ = self.board.occ();
+            let threatened = self.threatened & !TABLES.king[opp_king];
+
+            let mut board = self.board.clone();
+            board.black ^= true;
+            board.remove_takeable_empty();
+            board.update_hash(&self.board);
+
+            for sq in LocStack(self.board.castling_rooks() & self.cur_occ) {
+                let (threat, empty, diff) =
+                    TABLES.castles[self.board.black as usize][kingloc % 8][sq % 8];
+
+                if occ & empty == 0 && threatened & threat == 0 {
+                    let mut board2 = board.clone();
+                    board2.b ^= diff;
+                    board2.b ^=
+                        u64x4::from_array([0, 0, 0, board2.castling_rooks() & self.cur_occ]);
+                    board2.update_hash(&board);
+
+                    self.moves.push(board2);
+                }
+            }
+        }
+
+        self.update_variant_moves();
+
+        let board = &self.board;
+        self.moves.retain(|b| board.is_atomic_legal(b));
+    }
+}
+
+#[test]
+fn t_atomic() {
+    // captures explode the capturer and the pieces around it, but not pawns
+    let board = Board::from_fen("rnbqkbnr/ppp2ppp/8/3pp3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq -")
+        .with_variant(Variant::Atomic);
+    let board2 = board.do_move("f3e5".parse().unwrap());
+
+    assert_eq!(
+        board2,
+        Board::from_fen("rnbqkbnr/ppp2ppp/8/3p4/4P3/8/PPPP1PPP/RNBQKB1R b KQkq -")
+            .with_variant(Variant::Atomic)
+    );
+
+    let mut board3 = board2.clone();
+    board3.init_hash();
+    assert_eq!(board2.hash, board3.hash);
+
+    // Qxf2 explodes the white king
+    let board = Board::from_fen("rnb1kbnr/pppppppp/8/8/7q/8/PPPPPPPP/RNBQKBNR b KQkq -")
+        .with_variant(Variant::Atomic);
+    let board2 = board.do_move("h4f2".parse().unwrap());
+
+    assert!(board2.is_variant_loss());
+    assert_eq!(
+        board2,
+        Board::from_fen("rnb1kbnr/pppppppp/8/8/8/8/PPPPP1PP/RNBQ3R w kq -")
+            .with_variant(Variant::Atomic)
+    );
+
+    let mut generator = MoveGenerator::new(board);
+    generator.gen_moves();
+
+    assert!(generator.moves.contains(&board2));
+}
+
+#[test]
+fn t_atomic_legality() {
+    use crate::game::*;
+    use crate::types::*;
+
+    // kings can't capture, and touching kings are never in check
+    let board = Board::from_fen("8/8/8/8/8/8/3kq3/4K3 w - -").with_variant(Variant::Atomic);
+    let mut generator = MoveGenerator::new(board.clone());
+    generator.gen_moves();
+
+    assert_eq!(generator.checks, 0);
+    assert_eq!(
+        generator.moves,
+        vec![board.do_move("e1d1".parse().unwrap())]
+    );
+
+    // captures that explode the own king are illegal
+    let board = Board::from_fen("4k3/8/8/8/8/8/R2n4/4K3 w - -").with_variant(Variant::Atomic);
+    let mut generator = MoveGenerator::new(board.clone());
+    generator.gen_moves();
+
+    assert!(!generator
+        .moves
+        .contains(&board.do_move("a2d2".parse().unwrap())));
+    assert!(generator.moves.iter().all(|b| b.kings() & b.white() != 0));
+
+    // pinned pieces can move if they explode the enemy king
+    let board = Board::from_fen("7q/8/8/8/4k3/3p4/1N6/K7 w - -").with_variant(Variant::Atomic);
+    let mut generator = MoveGenerator::new(board.clone());
+    generator.gen_moves();
+
+    assert!(generator
+        .moves
+        .contains(&board.do_move("b2d3".parse().unwrap())));
+    assert!(!generator
+        .moves
+        .contains(&board.do_move("b2d1".parse().unwrap())));
+
+    let mut game = Game::new(board);
+    game.push("b2d3".parse().unwrap());
+
+    assert_eq!(game.outcome(), Some(Outcome::VariantWin(Color::White)));
+}
+
+#[test]
+fn t_atomic_round_trip() {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    // king moves give up castling rights
+    let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq -").with_variant(Variant::Atomic);
+    let next = board.do_move("e8f8".parse().unwrap());
+    assert_eq!(next.to_fen(false), "r4k1r/8/8/8/8/8/8/R3K2R w KQ -");
+
+    let mut generator = MoveGenerator::new(next.clone());
+    generator.gen_moves();
+
+    for b in &generator.moves {
+        assert_eq!(*b, next.do_move(next.get_move(b, false)));
+    }
+
+    // every generated move is reproduced by do_move
+    let mut rng = StdRng::seed_from_u64(0);
+
+    for _ in 0..20 {
+        let mut board = Board::from_fen(START_FEN).with_variant(Variant::Atomic);
+
+        for _ in 0..100 {
+            let mut generator = MoveGenerator::new(board.clone());
+            generator.gen_moves();
+
+            if generator.moves.is_empty() || board.is_variant_loss() {
+                break;
+            }
+
+            for b in &generator.moves {
+                assert_eq!(*b, board.do_move(board.get_move(b, false)));
+            }
+
+            board = generator.moves[rng.gen_range(0..generator.moves.len())].clone();
+        }
+    }
+}
@@ -1,4 +1,6 @@
 use crate::gen_tables::*;
+use crate::types::*;
+use crate::variant::*;
 pub use std::simd::{num::SimdUint, u64x4};
 
 macro_rules! gen_line {
@@ -42,7 +44,10 @@ macro_rules! get_piece {
 pub const FEN_PIECES: &str = "_PNBQKRR_pnbqkrr";
 pub const FILES: &str = "hgfedcba";
 pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+// h1, square 0, is a light square
+pub const LIGHT_SQUARES: u64 = 0xAA55AA55AA55AA55;
 
+// panics on malformed input, use `Square::from_str` for untrusted strings
 pub fn sq_from_str(s: &str) -> usize {
     let mut word = s.chars();
 
@@ -67,6 +72,13 @@ pub struct Board {
     pub b: u64x4,
     pub black: bool,
     pub hash: u64,
+    pub variant: Variant,
+    // checks each side still has to give to win a three-check game
+    pub checks: [u8; 2],
+    // crazyhouse pieces in hand, indexed by color and piece code
+    pub pockets: [[u8; 8]; 2],
+    // crazyhouse promoted pieces, which go back to the pocket as pawns
+    pub promoted: u64,
 }
 
 impl Board {
@@ -75,6 +87,10 @@ impl Board {
             b: u64x4::splat(0),
             black: false,
             hash: 0,
+            variant: Variant::Standard,
+            checks: [3, 3],
+            pockets: [[0; 8]; 2],
+            promoted: 0,
         }
     }
 
@@ -115,7 +131,7 @@ impl Board {
         let mut out = Board {
             b: u64x4::from_slice(&out[..]),
             black,
-            hash: 0,
+            ..Board::new()
         };
 
         out.init_hash();
@@ -132,7 +148,7 @@ impl Board {
             for x in (0..8).rev() {
                 let sq = squares[x + y * 8] as usize;
 
-                if sq % 8 == 0 {
+                if sq.is_multiple_of(8) {
                     empty += 1;
                 } else {
                     if empty > 0 {
@@ -140,6 +156,10 @@ impl Board {
                         empty = 0;
                     }
                     out += &FEN_PIECES[sq..sq + 1];
+
+                    if self.promoted & 1 << (x + y * 8) != 0 {
+                        out += "~";
+                    }
                 }
             }
 
@@ -152,54 +172,30 @@ impl Board {
             }
         }
 
+        if self.variant == Variant::Crazyhouse {
+            out += &format!("[{}]", self.pocket_fen());
+        }
+
         if self.black {
             out += " b ";
         } else {
             out += " w ";
         }
 
-        let mut castles = Vec::new();
-
-        for sq in LocStack(self.castling_white_rooks()) {
-            if c960 {
-                let file = sq % 8;
-                castles.push(FILES.chars().nth(file).unwrap().to_ascii_uppercase());
-            } else if sq == 0 {
-                castles.push('K');
-            } else if sq == 7 {
-                castles.push('Q');
-            }
-        }
-
-        for sq in LocStack(self.castling_black_rooks()) {
-            if c960 {
-                let file = sq % 8;
-                castles.push(FILES.chars().nth(file).unwrap());
-            } else if sq == 56 {
-                castles.push('k');
-            } else if sq == 63 {
-                castles.push('q');
-            }
-        }
-
-        if castles.is_empty() {
-            out += "-";
-        } else {
-            castles.sort();
-
-            out += &castles.into_iter().collect::<String>();
-        }
+        out += &self.castling_rights().fen(c960);
 
         out += " ";
 
-        let ep_squares = self.takeable_empties();
-
-        if ep_squares != 0 {
-            out += &str_from_sq(ep_squares.trailing_zeros() as usize);
+        if let Some(sq) = self.ep_square() {
+            out += &sq.to_string();
         } else {
             out += "-";
         }
 
+        if self.variant == Variant::ThreeCheck {
+            out += &format!(" +{}+{}", self.checks[0], self.checks[1]);
+        }
+
         out
     }
 
@@ -210,8 +206,20 @@ impl Board {
         let mut y = 7;
         let mut x = 7;
 
-        for c in words.next().unwrap().chars() {
+        let mut placement = words.next().unwrap();
+        let mut pockets = None;
+
+        if let Some((p, pocket)) = placement.split_once('[') {
+            placement = p;
+            pockets = Some(parse_pockets(pocket.trim_end_matches(']')));
+        }
+
+        let mut promoted = 0;
+        let mut last = 0;
+
+        for c in placement.chars() {
             match c {
+                '~' => promoted |= 1 << last,
                 '/' => {
                     y -= 1;
                     x = 7
@@ -225,7 +233,8 @@ impl Board {
                 }
                 _ => {
                     if let Some(i) = FEN_PIECES.find(c) {
-                        squares[(x + y * 8) as usize] = i as u8;
+                        last = x + y * 8;
+                        squares[last as usize] = i as u8;
 
                         x = x.saturating_sub(1);
                     }
@@ -257,13 +266,104 @@ impl Board {
             }
         }
 
-        let word = words.next().unwrap();
+        if let Some(Ok(sq)) = words.next().map(str::parse::<Square>) {
+            squares[sq.index()] = 8;
+        }
+
+        let mut out = Board::from_squarewise(&squares, black);
+
+        out.promoted = promoted & out.occ();
+        out.pockets = pockets.unwrap_or_default();
 
-        if word != "-" {
-            squares[sq_from_str(word)] = 8;
+        for word in words {
+            if let Some(checks) = parse_checks(word) {
+                out.checks = checks;
+            }
         }
 
-        Board::from_squarewise(&squares, black)
+        out.init_hash();
+        out
+    }
+
+    /// The back rank of Chess960 start position `index` (0..960) in Scharnagl
+    /// numbering, from the a-file to the h-file.
+    pub fn chess960_back_rank(index: u16) -> [PieceKind; 8] {
+        const KNIGHTS: [(usize, usize); 10] = [
+            (0, 1),
+            (0, 2),
+            (0, 3),
+            (0, 4),
+            (1, 2),
+            (1, 3),
+            (1, 4),
+            (2, 3),
+            (2, 4),
+            (3, 4),
+        ];
+
+        let mut rank = [None; 8];
+        let mut n = index as usize % 960;
+
+        rank[n % 4 * 2 + 1] = Some(PieceKind::Bishop);
+        n /= 4;
+        rank[n % 4 * 2] = Some(PieceKind::Bishop);
+        n /= 4;
+
+        let mut place = |i: usize, kind: PieceKind| {
+            let file = (0..8).filter(|f| rank[*f].is_none()).nth(i).unwrap();
+            rank[file] = Some(kind);
+        };
+
+        place(n % 6, PieceKind::Queen);
+        n /= 6;
+
+        let (n1, n2) = KNIGHTS[n];
+        place(n2, PieceKind::Knight);
+        place(n1, PieceKind::Knight);
+
+        place(0, PieceKind::Rook);
+        place(0, PieceKind::King);
+        place(0, PieceKind::Rook);
+
+        rank.map(Option::unwrap)
+    }
+
+    /// Chess960 start position `index` (0..960), with 518 being the
+    /// standard start position.
+    pub fn chess960(index: u16) -> Self {
+        Self::dfrc(index, index)
+    }
+
+    /// Double Fischer Random start position, with independent Chess960
+    /// arrangements for white and black.
+    pub fn dfrc(white: u16, black: u16) -> Self {
+        let mut fen = String::new();
+        let mut castles = String::new();
+
+        for (i, index) in [black, white].iter().enumerate() {
+            let rank = Self::chess960_back_rank(*index);
+            let color = Color::from_black(i == 0);
+
+            if i == 1 {
+                fen += "/pppppppp/8/8/8/8/PPPPPPPP/";
+            }
+
+            for (file, kind) in rank.iter().enumerate() {
+                fen.push(Piece::new(color, *kind).to_char());
+
+                if *kind == PieceKind::Rook {
+                    let c = (b'a' + file as u8) as char;
+
+                    castles.push(if color.is_black() {
+                        c
+                    } else {
+                        c.to_ascii_uppercase()
+                    });
+                }
+            }
+        }
+
+        Self::from_fen(&format!("{} w {} -", fen, castles))
     }
 
     get_piece!(pawns, [M, 0, 0, 0], [0, M, M, 0]);
@@ -316,7 +416,7 @@ impl Board {
         let mut out = Board {
             b: u64x4::from_array([self.black_pawns(), 0, 0, self.pawns()]),
             black: self.black,
-            hash: 0,
+            ..Board::new()
         };
 
         out.init_hash();
@@ -331,6 +431,9 @@ impl Board {
 pub struct Hasher {
     bits: [[u64; 64]; 4],
     black: u64,
+    checks: [[u64; 4]; 2],
+    pockets: [[[u64; 16]; 8]; 2],
+    promoted: [u64; 64],
 }
 
 lazy_static! {
@@ -350,10 +453,56 @@ impl Hasher {
             }
         }
 
+        // three remaining checks hashes to zero so that standard chess
+        // positions are unaffected
+        let mut checks = [[0u64; 4]; 2];
+
+        for row in &mut checks {
+            for x in &mut row[..3] {
+                *x = rng.gen();
+            }
+        }
+
+        // as are empty pockets
+        let mut pockets = [[[0u64; 16]; 8]; 2];
+
+        for x in pockets.iter_mut().flatten().flat_map(|p| &mut p[1..]) {
+            *x = rng.gen();
+        }
+
+        let mut promoted = [0u64; 64];
+
+        for x in &mut promoted {
+            *x = rng.gen();
+        }
+
         Self {
             bits,
             black: rng.gen(),
+            checks,
+            pockets,
+            promoted,
+        }
+    }
+
+    fn hash_pockets(&self, pockets: &[[u8; 8]; 2]) -> u64 {
+        let mut hash = 0;
+
+        for (keys, pocket) in self.pockets.iter().zip(pockets) {
+            for (keys, n) in keys.iter().zip(pocket) {
+                hash ^= keys[(*n).min(15) as usize];
+            }
         }
+
+        hash
+    }
+
+    fn hash_promoted(&self, promoted: u64) -> u64 {
+        LocStack(promoted).fold(0, |hash, sq| hash ^ self.promoted[sq])
+    }
+
+    fn hash_checks(&self, checks: [u8; 2]) -> u64 {
+        self.checks[0][checks[0].min(3) as usize] ^ self.checks[1][checks[1].min(3) as usize]
     }
 
     fn hash_bits(&self, bits: u64x4) -> u64 {
@@ -377,6 +526,10 @@ impl Board {
             hash ^= HASHER.black;
         }
 
+        hash ^= HASHER.hash_checks(self.checks);
+        hash ^= HASHER.hash_pockets(&self.pockets);
+        hash ^= HASHER.hash_promoted(self.promoted);
+
         self.hash = hash;
     }
 
@@ -387,6 +540,16 @@ impl Board {
             hash ^= HASHER.black;
         }
 
+        if self.checks != prev.checks {
+            hash ^= HASHER.hash_checks(self.checks) ^ HASHER.hash_checks(prev.checks);
+        }
+
+        if self.pockets != prev.pockets {
+            hash ^= HASHER.hash_pockets(&self.pockets) ^ HASHER.hash_pockets(&prev.pockets);
+        }
+
+        hash ^= HASHER.hash_promoted(self.promoted ^ prev.promoted);
+
         self.hash = prev.hash ^ hash;
     }
 }
@@ -425,6 +588,43 @@ impl FromStr for Board {
 #[allow(unused_imports)]
 use test::Bencher;
 
+#[test]
+fn t_chess960() {
+    use std::collections::HashSet;
+
+    assert_eq!(Board::chess960(518), Board::from_fen(START_FEN));
+    assert_eq!(
+        Board::chess960(0).to_fen(true),
+        "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w FHfh -"
+    );
+    assert_eq!(
+        Board::dfrc(0, 959).to_fen(true),
+        "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w FHac -"
+    );
+
+    let mut seen = HashSet::new();
+
+    for i in 0..960 {
+        let board = Board::chess960(i);
+        let bishops = board.white_bishops();
+
+        assert!(seen.insert(board.hash));
+        assert_eq!(bishops.count_ones(), 2);
+        assert_eq!((bishops & 0x55).count_ones(), 1);
+
+        let king = board.king_square(Color::White).unwrap();
+        let rights = board.castling_rights();
+
+        assert!(rights.kingside(Color::White).is_some());
+        assert!(rights.queenside(Color::White).is_some());
+        assert_eq!(
+            rights.kingside(Color::Black).unwrap().file(),
+            rights.kingside(Color::White).unwrap().file()
+        );
+        assert!(rights.rooks(Color::White).all(|r| r.rank() == king.rank()));
+    }
+}
+
 #[test]
 fn t_hash() {
     let mut board1 = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -");
@@ -1,8 +1,15 @@
 // this is a submodule of gen_moves so that it can have access to
 // MoveGenerator internals
 use super::*;
+use crate::active::*;
 use crate::board::*;
-use crate::tt::*;
+
+use std::fmt;
+use std::fs;
+use std::slice;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
 fn northfill(mut b: u64) -> u64 {
     b |= b << 32;
@@ -24,6 +31,47 @@ fn adjacent(f: u8) -> u8 {
     f << 1 | f >> 1
 }
 
+// every square on the files set in `f`
+fn file_mask(f: u8) -> u64 {
+    f as u64 * 0x0101010101010101
+}
+
+// white pawns whose stop square is attacked by black pawns and can't be
+// defended by other white pawns, leaving isolated pawns out
+fn backward_pawns(w: u64, b: u64) -> u64 {
+    let files = southfill(w) as u8;
+    let stops = (w & file_mask(adjacent(files))) << 8;
+
+    (stops & b_pawn_threats(b) & !northfill(w_pawn_threats(w))) >> 8
+}
+
+// white pawns that aren't passed but have no black pawns ahead of them and
+// at least as many white pawns beside or behind them on the next files as
+// there are black pawns on those files ahead
+fn candidate_passers(w: u64, b: u64) -> u64 {
+    let mut out = 0;
+
+    for sq in LocStack(w & southfill(b_pawn_threats(b)) & !southfill(b)) {
+        let file = northfill(1 << sq);
+        let sentries = w_pawn_threats(file) & b;
+        let helpers = b_pawn_threats(southfill(1 << sq << 8)) & w;
+
+        if helpers.count_ones() >= sentries.count_ones() {
+            out |= 1 << sq;
+        }
+    }
+
+    out
+}
+
+// the number of king moves between two squares
+fn distance(a: usize, b: usize) -> i32 {
+    let files = (a % 8) as i32 - (b % 8) as i32;
+    let ranks = (a / 8) as i32 - (b / 8) as i32;
+
+    files.abs().max(ranks.abs())
+}
+
 pub fn w_pawn_threats(pawns: u64) -> u64 {
     (pawns & 0x7f7f7f7f7f7f7f7f) << 9 | (pawns & 0xfefefefefefefefe) << 7
 }
@@ -34,165 +82,787 @@ pub fn b_pawn_threats(pawns: u64) -> u64 {
 
 pub const CHECKMATE: i32 = 25600;
 
+// phase of a position with all the pieces still on the board
+pub const MAX_PHASE: i32 = 24;
+
+// scale factor of endgames that play out normally
+pub const SCALE_NORMAL: i32 = 64;
+
+/// A (middlegame, endgame) pair of values, blended by the game phase.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Score(pub i32, pub i32);
+
+impl Score {
+    pub fn taper(self, phase: i32) -> i32 {
+        (self.0 * phase + self.1 * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+// written as "mg/eg", or a single value used for both halves
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(&format!("{}/{}", self.0, self.1))
+    }
+}
+
+impl FromStr for Score {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |n: &str| n.parse::<i32>().map_err(|_| ());
+
+        match s.split_once('/') {
+            Some((mg, eg)) => Ok(Score(parse(mg)?, parse(eg)?)),
+            None => parse(s).map(Score::from),
+        }
+    }
+}
+
+impl From<i32> for Score {
+    fn from(n: i32) -> Self {
+        Score(n, n)
+    }
+}
+
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+impl Add for Score {
+    type Output = Score;
+
+    fn add(self, other: Score) -> Score {
+        Score(self.0 + other.0, self.1 + other.1)
+    }
+}
+
+impl Sub for Score {
+    type Output = Score;
+
+    fn sub(self, other: Score) -> Score {
+        Score(self.0 - other.0, self.1 - other.1)
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, other: Score) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Score {
+    fn sub_assign(&mut self, other: Score) {
+        *self = *self - other;
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+
+    fn neg(self) -> Score {
+        Score(-self.0, -self.1)
+    }
+}
+
+impl Mul<i32> for Score {
+    type Output = Score;
+
+    fn mul(self, n: i32) -> Score {
+        Score(self.0 * n, self.1 * n)
+    }
+}
+
+impl Mul<Score> for i32 {
+    type Output = Score;
+
+    fn mul(self, score: Score) -> Score {
+        score * self
+    }
+}
+
+impl std::iter::Sum for Score {
+    fn sum<I: Iterator<Item = Score>>(iter: I) -> Score {
+        iter.fold(Score(0, 0), Score::add)
+    }
+}
+
+/// The terms an eval trace breaks the eval down into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Term {
+    Material,
+    Imbalance,
+    PawnPst,
+    KnightPst,
+    BishopPst,
+    RookPst,
+    QueenPst,
+    KingPst,
+    KnightMobility,
+    BishopMobility,
+    RookMobility,
+    QueenMobility,
+    KingMobility,
+    PawnChains,
+    PassedPawns,
+    PasserSupport,
+    DoubledPawns,
+    IsolatedPawns,
+    BackwardPawns,
+    PawnIslands,
+    CandidatePassers,
+    ConnectedPassers,
+    Phalanxes,
+    PawnCenter,
+    KingShelter,
+    Castling,
+    KingAttacks,
+    BishopPair,
+    RookFiles,
+    Outposts,
+    BadBishops,
+    TrappedPieces,
+    Threats,
+    VariantBonus,
+}
+
+impl Term {
+    pub const ALL: [Term; 34] = [
+        Term::Material,
+        Term::Imbalance,
+        Term::PawnPst,
+        Term::KnightPst,
+        Term::BishopPst,
+        Term::RookPst,
+        Term::QueenPst,
+        Term::KingPst,
+        Term::KnightMobility,
+        Term::BishopMobility,
+        Term::RookMobility,
+        Term::QueenMobility,
+        Term::KingMobility,
+        Term::PawnChains,
+        Term::PassedPawns,
+        Term::PasserSupport,
+        Term::DoubledPawns,
+        Term::IsolatedPawns,
+        Term::BackwardPawns,
+        Term::PawnIslands,
+        Term::CandidatePassers,
+        Term::ConnectedPassers,
+        Term::Phalanxes,
+        Term::PawnCenter,
+        Term::KingShelter,
+        Term::Castling,
+        Term::KingAttacks,
+        Term::BishopPair,
+        Term::RookFiles,
+        Term::Outposts,
+        Term::BadBishops,
+        Term::TrappedPieces,
+        Term::Threats,
+        Term::VariantBonus,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Term::Material => "Material",
+            Term::Imbalance => "Imbalance",
+            Term::PawnPst => "Pawn PST",
+            Term::KnightPst => "Knight PST",
+            Term::BishopPst => "Bishop PST",
+            Term::RookPst => "Rook PST",
+            Term::QueenPst => "Queen PST",
+            Term::KingPst => "King PST",
+            Term::KnightMobility => "Knight mobility",
+            Term::BishopMobility => "Bishop mobility",
+            Term::RookMobility => "Rook mobility",
+            Term::QueenMobility => "Queen mobility",
+            Term::KingMobility => "King mobility",
+            Term::PawnChains => "Pawn chains",
+            Term::PassedPawns => "Passed pawns",
+            Term::PasserSupport => "Passer support",
+            Term::DoubledPawns => "Doubled pawns",
+            Term::IsolatedPawns => "Isolated pawns",
+            Term::BackwardPawns => "Backward pawns",
+            Term::PawnIslands => "Pawn islands",
+            Term::CandidatePassers => "Candidate passers",
+            Term::ConnectedPassers => "Connected passers",
+            Term::Phalanxes => "Phalanxes",
+            Term::PawnCenter => "Pawn center",
+            Term::KingShelter => "King shelter",
+            Term::Castling => "Castling",
+            Term::KingAttacks => "King attacks",
+            Term::BishopPair => "Bishop pair",
+            Term::RookFiles => "Rook files",
+            Term::Outposts => "Outposts",
+            Term::BadBishops => "Bad bishops",
+            Term::TrappedPieces => "Trapped pieces",
+            Term::Threats => "Threats",
+            Term::VariantBonus => "Variant",
+        }
+    }
+}
+
+/// Collects the terms of the eval, each given for one side from that side's
+/// point of view.
+pub trait EvalSink {
+    // whether the terms are kept apart, which the pawn hash table can't do
+    const TRACE: bool;
+
+    fn record(&mut self, term: Term, black: bool, score: Score);
+}
+
+// the real eval just sums everything up from white's point of view
+impl EvalSink for Score {
+    const TRACE: bool = false;
+
+    #[inline]
+    fn record(&mut self, _: Term, black: bool, score: Score) {
+        *self += invert_if(black, score);
+    }
+}
+
+/// The eval of a position broken down by term and side.
 #[derive(Clone, Debug)]
-pub struct EvalParams {
-    pub chain_weight: i32,
-    pub passed_weight: i32,
-    pub doubled_weight: i32,
-    pub isolated_weight: i32,
-    pub king_pawn_weight: i32,
+pub struct EvalTrace {
+    // indexed by term and color
+    pub terms: [[Score; 2]; Term::ALL.len()],
+    pub phase: i32,
+    // how much of the endgame total counts, out of `SCALE_NORMAL`
+    pub scale: i32,
+    pub black: bool,
+    // the eval itself, from the side to move's point of view
+    pub score: i32,
+}
+
+// too many terms for arrays to derive this
+impl Default for EvalTrace {
+    fn default() -> Self {
+        Self {
+            terms: [[Score(0, 0); 2]; Term::ALL.len()],
+            phase: 0,
+            scale: SCALE_NORMAL,
+            black: false,
+            score: 0,
+        }
+    }
+}
+
+impl EvalTrace {
+    pub fn get(&self, term: Term) -> [Score; 2] {
+        self.terms[term as usize]
+    }
+
+    /// Sum of all the terms from white's point of view, before tapering.
+    pub fn total(&self) -> Score {
+        self.terms.iter().map(|[w, b]| *w - *b).sum()
+    }
+}
 
-    pub castle_bonus: i32,
+impl EvalSink for EvalTrace {
+    const TRACE: bool = true;
 
-    pub knight_move_weight: i32,
-    pub bishop_move_weight: i32,
-    pub rook_move_weight: i32,
-    pub queen_move_weight: i32,
-    pub king_move_weight: i32,
+    fn record(&mut self, term: Term, black: bool, score: Score) {
+        self.terms[term as usize][black as usize] += score;
+    }
+}
 
-    pub pawn_weight: i32,
-    pub knight_weight: i32,
-    pub bishop_weight: i32,
-    pub rook_weight: i32,
-    pub queen_weight: i32,
-    pub king_weight: i32,
+impl std::fmt::Display for EvalTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let row = |f: &mut std::fmt::Formatter<'_>, name: &str, scores: [Score; 3]| {
+            write!(f, "{:>16} |", name)?;
 
-    pub psts: [[i32; 64]; 16],
+            for s in scores {
+                write!(f, " {:>6} {:>6} |", s.0, s.1)?;
+            }
+            writeln!(f)
+        };
+
+        writeln!(
+            f,
+            "{:>16} | {:^13} | {:^13} | {:^13} |",
+            "Term", "White", "Black", "Total"
+        )?;
+        writeln!(
+            f,
+            "{:>16} |{}",
+            "",
+            format!(" {:>6} {:>6} |", "MG", "EG").repeat(3)
+        )?;
+        writeln!(f, "{}", "-".repeat(66))?;
+
+        for term in Term::ALL {
+            let [w, b] = self.get(term);
+
+            row(f, term.name(), [w, b, w - b])?;
+        }
+
+        writeln!(f, "{}", "-".repeat(66))?;
+        writeln!(
+            f,
+            "{:>16} | {:13} | {:13} | {:>6} {:>6} |",
+            "Total",
+            "",
+            "",
+            self.total().0,
+            self.total().1
+        )?;
+        writeln!(f)?;
+        writeln!(f, "Phase: {}/{}", self.phase, MAX_PHASE)?;
+        writeln!(f, "Scale: {}/{}", self.scale, SCALE_NORMAL)?;
+        writeln!(
+            f,
+            "Eval: {} (white side), {} (side to move)",
+            invert_if(self.black, self.score),
+            self.score
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EvalParams {
+    pub chain_weight: Score,
+    // passed pawns by rank, counted from their own side
+    pub passed_weights: [Score; 8],
+    pub doubled_weight: Score,
+    pub isolated_weight: Score,
+    // pawns that can't be defended by other pawns and can't safely advance
+    pub backward_weight: Score,
+    // per group of files with pawns next to each other
+    pub island_weight: Score,
+    // pawns on files without enemy pawns ahead, with at least as much help
+    // from own pawns as there are enemy pawns to get past
+    pub candidate_weight: Score,
+    // passed pawns with another one on a file next to them
+    pub connected_passer_weight: Score,
+    // pawns with another one right beside them
+    pub phalanx_weight: Score,
+    // center squares occupied or attacked by pawns
+    pub pawn_center_weight: Score,
+
+    pub castle_bonus: Score,
+
+    // attack units for each enemy piece bearing on the king zone, and for
+    // each safe check it has, indexed by piece code
+    pub king_attack_weights: [Score; 8],
+    pub safe_check_weights: [Score; 8],
+    // the penalty for a number of attack units, each half of the table
+    // looked up with its own half of the units
+    pub king_safety_table: [Score; 32],
+    // the closest own and enemy pawns in front of the king on each of its
+    // files, indexed by rank counted from the king's side, 0 being no pawn
+    pub shelter_weights: [Score; 8],
+    pub storm_weights: [Score; 8],
+
+    pub knight_move_weight: Score,
+    pub bishop_move_weight: Score,
+    pub rook_move_weight: Score,
+    pub queen_move_weight: Score,
+    pub king_move_weight: Score,
+
+    pub bishop_pair_weight: Score,
+    pub rook_open_weight: Score,
+    pub rook_semi_open_weight: Score,
+    // a rook on the seventh rank, pinning enemy pawns or the king down
+    pub rook_seventh_weight: Score,
+    // minor pieces on squares protected by pawns that enemy pawns can't
+    // drive them away from
+    pub knight_outpost_weight: Score,
+    pub bishop_outpost_weight: Score,
+    // per own pawn on the bishop's color
+    pub bad_bishop_weight: Score,
+    // a bishop shut in on a7 or h7, and a rook stuck behind its uncastled
+    // king
+    pub trapped_bishop_weight: Score,
+    pub trapped_rook_weight: Score,
+
+    // per enemy piece that is attacked and not defended
+    pub hanging_weight: Score,
+    // per enemy piece attacked by a pawn, rook or queen attacked by a minor
+    // piece, and queen attacked by a rook
+    pub pawn_threat_weight: Score,
+    pub minor_threat_weight: Score,
+    pub rook_threat_weight: Score,
+    // per enemy piece a pawn can attack by safely moving forward
+    pub pawn_push_threat_weight: Score,
+    // per own bishop or rook lined up with the enemy queen behind one piece
+    pub weak_queen_weight: Score,
+
+    // passed pawns with nothing in front of them, by rank
+    pub free_passer_weights: [Score; 8],
+    // the own king next to a passed pawn
+    pub passer_support_weight: Score,
+    // per square of the kings' distance to the promotion square, times the
+    // ranks the pawn has advanced
+    pub passer_own_king_weight: Score,
+    pub passer_enemy_king_weight: Score,
+    pub rook_behind_passer_weight: Score,
+    // a passed pawn the enemy king can't catch, with no enemy pieces left
+    pub unstoppable_passer_weight: Score,
+
+    pub pawn_weight: Score,
+    pub knight_weight: Score,
+    pub bishop_weight: Score,
+    pub rook_weight: Score,
+    pub queen_weight: Score,
+    pub king_weight: Score,
+    // in sixteenths, per piece of each kind times the own and enemy pieces of
+    // each kind up to it, for pawns, knights, bishops, rooks and queens in
+    // that order, so kinds `i >= j` go at `i * (i + 1) / 2 + j`
+    pub imbalance_ours: [Score; 15],
+    pub imbalance_theirs: [Score; 15],
+
+    pub psts: [[Score; 64]; 16],
+
+    // indexed by the number of checks a side still has to give
+    pub check_weights: [Score; 4],
+    // indexed by the king's distance to the center squares
+    pub hill_weights: [Score; 4],
+    // crazyhouse pieces in hand, indexed by piece code
+    pub pocket_weights: [Score; 8],
+    // empty squares next to the enemy king, per piece in hand
+    pub drop_danger_weight: Score,
+
+    // how far outside the search window material and piece-square tables
+    // can be before the rest of the eval is skipped
+    pub lazy_margin: Score,
+
+    // told apart by pawn tables, which clear themselves when it changes. Only
+    // weights passed to `set_active` and the antichess ones get a new one, so
+    // weights changed in place need their tables cleared by hand
+    pub(crate) generation: u64,
+}
+
+// middlegame halves of the piece-square tables
+const MIDDLEGAME_PSTS: [[i32; 64]; 16] = [
+    [
+        // Empty
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0,
+    ],
+    [
+        // White Pawn
+        0, 0, 0, 0, 0, 0, 0, 0, -8, -5, -2, -23, -27, 2, -2, -2, 4, 2, -11, -7, -21, 0, -4, -5, 0,
+        5, -2, 8, 9, 1, 10, 3, 5, 13, 15, 17, 26, 18, 28, 19, 39, 54, 47, 42, 42, 57, 61, 77, 89,
+        107, 84, 84, 93, 97, 137, 89, 0, 0, 0, 0, 0, 0, 0, 0,
+    ],
+    [
+        // White Knight
+        29, -19, -26, -7, -20, -8, -14, -33, -3, 6, -6, 14, 7, 6, 38, -15, -9, 28, 16, 21, 8, 8, 6,
+        -18, -5, 31, 24, 24, 13, 10, 10, -14, 61, 24, 20, 47, 38, 26, 22, 15, 20, 51, 33, 55, 38,
+        42, 41, -16, -7, 24, 48, 1, 20, 31, 2, -42, -35, 34, 13, 16, 60, -7, 46, -6,
+    ],
+    [
+        // White bishop
+        37, 37, -17, 10, -19, 5, -9, 63, 44, 31, 7, 14, 16, 13, 40, 65, 3, 20, 16, 19, 15, 21, 32,
+        35, -8, 8, 32, 7, 9, 13, 17, 31, -26, 9, 20, 21, 17, 22, -1, 18, 40, 18, 23, 17, 48, 16,
+        33, 7, -12, 5, 6, 34, 14, 11, 32, 3, 16, -3, 13, 17, 28, 8, 33, 41,
+    ],
+    [
+        // White Queen
+        39, -82, -7, -11, -11, -26, -26, 45, -63, -4, -23, 2, -11, 0, 5, 12, 1, -7, 7, -13, -9, 10,
+        -3, -27, 15, 25, -5, -18, 4, -16, -8, -15, 10, 20, 34, 18, 13, -6, -45, 3, 37, 66, 70, 34,
+        48, -25, 27, -19, 59, 64, 50, 64, 10, 19, -30, -19, 20, -3, 51, 46, 23, 51, 17, 11,
+    ],
+    [
+        // White King
+        -50, -8, -28, -6, -45, -7, -13, 12, -23, -4, 9, 7, 4, 1, 4, 22, -29, 6, 13, 14, 18, 9, 17,
+        20, 0, 27, 35, 39, 47, 37, 35, -2, 26, 48, 61, 70, 68, 75, 58, 28, 45, 64, 85, 74, 78, 80,
+        95, 91, 94, 58, 72, 90, 91, 71, 93, 455, -22, 71, 51, 142, 192, 170, 548, 588,
+    ],
+    [
+        // White Rook
+        -15, 7, 21, 19, 21, 22, 22, -5, -12, -10, 17, 15, 16, 13, -8, -8, -12, 30, 11, 16, 22, -1,
+        3, 5, 12, 3, 25, 10, 14, 28, 16, 13, 30, 6, 40, 33, 43, 28, 26, 24, 20, 31, 29, 37, 42, 35,
+        32, 25, 24, 39, 48, 56, 62, 35, 43, 31, 27, 25, 43, 44, 39, 26, 33, 34,
+    ],
+    [
+        // White Uncastled Rook
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0,
+    ],
+    [
+        // Empty
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0,
+    ],
+    [
+        // Black Pawn
+        0, 0, 0, 0, 0, 0, 0, 0, 66, 115, 115, 113, 95, 122, 142, 87, 47, 75, 45, 46, 29, 78, 90, 79,
+        28, 30, 9, 17, 18, 13, 38, 32, -2, 6, -3, 5, 7, 6, 13, 4, -9, 2, -2, -15, -11, -5, -8, -1,
+        -14, 3, 5, -31, -32, 1, 5, -7, 0, 0, 0, 0, 0, 0, 0, 0,
+    ],
+    [
+        // Black Knight
+        -11, 32, -32, 20, 4, 40, 6, -15, 8, -34, 49, 25, 37, 36, 1, -37, 23, 31, 29, 36, 42, 33, 48,
+        -6, 48, 20, 22, 25, 41, 22, 20, 57, 1, 16, 34, 22, 9, 28, 21, -10, 5, 37, 17, 41, 10, 13,
+        0, -20, 19, 27, 10, 16, 11, 10, 41, 34, 17, -20, 4, -8, -4, 30, -8, 46,
+    ],
+    [
+        // Black Bishop
+        62, 13, 22, 15, 31, 30, 8, -2, -3, 19, 11, 22, 32, 4, -5, -11, 49, 49, 48, 11, 26, 23, 16,
+        10, 32, 14, 18, 19, 18, 23, 7, 14, 12, 15, 12, 21, 17, 23, 17, 27, 15, 27, 25, 11, 17, 36,
+        36, 23, 2, 49, 12, 26, 11, 18, 40, -4, 11, -15, -12, 39, 22, -6, 25, -6,
+    ],
+    [
+        // Black Queen
+        36, 80, 19, 55, 26, 31, 21, 6, 56, 9, 27, 59, -5, 9, 9, -15, 47, 45, 43, 21, 32, 15, 5, -4,
+        21, 17, 9, 17, 10, 1, -30, -30, 17, 24, 18, 16, 5, 13, 0, -13, 29, 14, -10, 16, -2, -21, 0,
+        -14, 19, -1, -22, 0, -11, 0, -40, -21, -110, -13, -44, -33, -7, -26, -55, -47,
+    ],
+    [
+        // Black King
+        -19, 19, 27, 10, 107, 112, 199, 317, 99, 2, 9, 58, 43, 49, 41, 280, 93, 43, 65, 72, 72, 73,
+        54, 143, 20, 35, 51, 67, 63, 51, 52, 97, -4, 23, 34, 37, 42, 39, 28, 12, -25, 2, 10, 15,
+        18, 28, 18, 21, -25, -6, 8, 2, 1, 16, 5, 7, -51, -7, -35, 0, -32, -1, -3, -25,
+    ],
+    [
+        // Black Rook
+        30, 20, 24, 31, 34, 33, 39, 27, 30, 33, 37, 55, 43, 39, 36, 24, 10, 35, 41, 44, 37, 37, 36,
+        21, 17, 6, 37, 23, 25, 31, 34, 25, -3, 14, 22, 5, 17, 20, 18, 30, -1, 3, 7, 10, 17, 17, 16,
+        2, -4, 8, 22, 5, 11, 20, 5, 6, -24, 14, 16, 19, 15, 17, 8, -9,
+    ],
+    [
+        // Black Uncastled Rook
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0,
+    ],
+];
+
+// the king belongs in the center once the queens are off
+const KING_ENDGAME_PST: [i32; 64] = [
+    -50, -30, -20, -10, -10, -20, -30, -50, -30, -10, 0, 10, 10, 0, -10, -30, -20, 0, 20, 30, 30,
+    20, 0, -20, -10, 10, 30, 40, 40, 30, 10, -10, -10, 10, 30, 40, 40, 30, 10, -10, -20, 0, 20, 30,
+    30, 20, 0, -20, -30, -10, 0, 10, 10, 0, -10, -30, -50, -30, -20, -10, -10, -20, -30, -50,
+];
+
+// the endgame tables start out as copies of the middlegame ones, apart from
+// the kings
+fn tapered_psts(middlegame: &[[i32; 64]; 16], king: &[i32; 64]) -> [[Score; 64]; 16] {
+    let mut out = [[Score(0, 0); 64]; 16];
+
+    for piece in 0..16 {
+        for sq in 0..64 {
+            let eg = if piece & 7 == 5 {
+                king[sq]
+            } else {
+                middlegame[piece][sq]
+            };
+
+            out[piece][sq] = Score(middlegame[piece][sq], eg);
+        }
+    }
+
+    out
 }
 
 impl Default for EvalParams {
     fn default() -> Self {
         EvalParams {
-            chain_weight: 11,
-            passed_weight: 41,
-            doubled_weight: -9,
-            isolated_weight: -13,
-            king_pawn_weight: 7,
-
-            castle_bonus: -4,
-
-            knight_move_weight: 5,
-            bishop_move_weight: 6,
-            rook_move_weight: 3,
-            queen_move_weight: 1,
-            king_move_weight: -5,
-
-            pawn_weight: 100,
-            knight_weight: 279,
-            bishop_weight: 293,
-            rook_weight: 466,
-            queen_weight: 866,
-            king_weight: 25600,
-
-            psts: [
-                [
-                    // Empty
-                    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                ],
-                [
-                    // White Pawn
-                    0, 0, 0, 0, 0, 0, 0, 0, -8, -5, -2, -23, -27, 2, -2, -2, 4, 2, -11, -7, -21, 0,
-                    -4, -5, 0, 5, -2, 8, 9, 1, 10, 3, 5, 13, 15, 17, 26, 18, 28, 19, 39, 54, 47,
-                    42, 42, 57, 61, 77, 89, 107, 84, 84, 93, 97, 137, 89, 0, 0, 0, 0, 0, 0, 0, 0,
-                ],
-                [
-                    // White Knight
-                    29, -19, -26, -7, -20, -8, -14, -33, -3, 6, -6, 14, 7, 6, 38, -15, -9, 28, 16,
-                    21, 8, 8, 6, -18, -5, 31, 24, 24, 13, 10, 10, -14, 61, 24, 20, 47, 38, 26, 22,
-                    15, 20, 51, 33, 55, 38, 42, 41, -16, -7, 24, 48, 1, 20, 31, 2, -42, -35, 34,
-                    13, 16, 60, -7, 46, -6,
-                ],
-                [
-                    // White bishop
-                    37, 37, -17, 10, -19, 5, -9, 63, 44, 31, 7, 14, 16, 13, 40, 65, 3, 20, 16, 19,
-                    15, 21, 32, 35, -8, 8, 32, 7, 9, 13, 17, 31, -26, 9, 20, 21, 17, 22, -1, 18,
-                    40, 18, 23, 17, 48, 16, 33, 7, -12, 5, 6, 34, 14, 11, 32, 3, 16, -3, 13, 17,
-                    28, 8, 33, 41,
-                ],
-                [
-                    // White Queen
-                    39, -82, -7, -11, -11, -26, -26, 45, -63, -4, -23, 2, -11, 0, 5, 12, 1, -7, 7,
-                    -13, -9, 10, -3, -27, 15, 25, -5, -18, 4, -16, -8, -15, 10, 20, 34, 18, 13, -6,
-                    -45, 3, 37, 66, 70, 34, 48, -25, 27, -19, 59, 64, 50, 64, 10, 19, -30, -19, 20,
-                    -3, 51, 46, 23, 51, 17, 11,
-                ],
-                [
-                    // White King
-                    -50, -8, -28, -6, -45, -7, -13, 12, -23, -4, 9, 7, 4, 1, 4, 22, -29, 6, 13, 14,
-                    18, 9, 17, 20, 0, 27, 35, 39, 47, 37, 35, -2, 26, 48, 61, 70, 68, 75, 58, 28,
-                    45, 64, 85, 74, 78, 80, 95, 91, 94, 58, 72, 90, 91, 71, 93, 455, -22, 71, 51,
-                    142, 192, 170, 548, 588,
-                ],
-                [
-                    // White Rook
-                    -15, 7, 21, 19, 21, 22, 22, -5, -12, -10, 17, 15, 16, 13, -8, -8, -12, 30, 11,
-                    16, 22, -1, 3, 5, 12, 3, 25, 10, 14, 28, 16, 13, 30, 6, 40, 33, 43, 28, 26, 24,
-                    20, 31, 29, 37, 42, 35, 32, 25, 24, 39, 48, 56, 62, 35, 43, 31, 27, 25, 43, 44,
-                    39, 26, 33, 34,
-                ],
-                [
-                    // White Uncastled Rook
-                    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                ],
-                [
-                    // Empty
-                    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                ],
-                [
-                    // Black Pawn
-                    0, 0, 0, 0, 0, 0, 0, 0, 66, 115, 115, 113, 95, 122, 142, 87, 47, 75, 45, 46, 29,
-                    78, 90, 79, 28, 30, 9, 17, 18, 13, 38, 32, -2, 6, -3, 5, 7, 6, 13, 4, -9, 2,
-                    -2, -15, -11, -5, -8, -1, -14, 3, 5, -31, -32, 1, 5, -7, 0, 0, 0, 0, 0, 0, 0,
-                    0,
-                ],
-                [
-                    // Black Knight
-                    -11, 32, -32, 20, 4, 40, 6, -15, 8, -34, 49, 25, 37, 36, 1, -37, 23, 31, 29, 36,
-                    42, 33, 48, -6, 48, 20, 22, 25, 41, 22, 20, 57, 1, 16, 34, 22, 9, 28, 21, -10,
-                    5, 37, 17, 41, 10, 13, 0, -20, 19, 27, 10, 16, 11, 10, 41, 34, 17, -20, 4, -8,
-                    -4, 30, -8, 46,
-                ],
-                [
-                    // Black Bishop
-                    62, 13, 22, 15, 31, 30, 8, -2, -3, 19, 11, 22, 32, 4, -5, -11, 49, 49, 48, 11,
-                    26, 23, 16, 10, 32, 14, 18, 19, 18, 23, 7, 14, 12, 15, 12, 21, 17, 23, 17, 27,
-                    15, 27, 25, 11, 17, 36, 36, 23, 2, 49, 12, 26, 11, 18, 40, -4, 11, -15, -12,
-                    39, 22, -6, 25, -6,
-                ],
-                [
-                    // Black Queen
-                    36, 80, 19, 55, 26, 31, 21, 6, 56, 9, 27, 59, -5, 9, 9, -15, 47, 45, 43, 21, 32,
-                    15, 5, -4, 21, 17, 9, 17, 10, 1, -30, -30, 17, 24, 18, 16, 5, 13, 0, -13, 29,
-                    14, -10, 16, -2, -21, 0, -14, 19, -1, -22, 0, -11, 0, -40, -21, -110, -13, -44,
-                    -33, -7, -26, -55, -47,
-                ],
-                [
-                    // Black King
-                    -19, 19, 27, 10, 107, 112, 199, 317, 99, 2, 9, 58, 43, 49, 41, 280, 93, 43, 65,
-                    72, 72, 73, 54, 143, 20, 35, 51, 67, 63, 51, 52, 97, -4, 23, 34, 37, 42, 39,
-                    28, 12, -25, 2, 10, 15, 18, 28, 18, 21, -25, -6, 8, 2, 1, 16, 5, 7, -51, -7,
-                    -35, 0, -32, -1, -3, -25,
-                ],
-                [
-                    // Black Rook
-                    30, 20, 24, 31, 34, 33, 39, 27, 30, 33, 37, 55, 43, 39, 36, 24, 10, 35, 41, 44,
-                    37, 37, 36, 21, 17, 6, 37, 23, 25, 31, 34, 25, -3, 14, 22, 5, 17, 20, 18, 30,
-                    -1, 3, 7, 10, 17, 17, 16, 2, -4, 8, 22, 5, 11, 20, 5, 6, -24, 14, 16, 19, 15,
-                    17, 8, -9,
-                ],
-                [
-                    // Black Uncastled Rook
-                    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                ],
+            chain_weight: Score(11, 8),
+            passed_weights: [
+                Score(0, 0),
+                Score(5, 10),
+                Score(8, 15),
+                Score(15, 25),
+                Score(35, 50),
+                Score(60, 90),
+                Score(90, 140),
+                Score(0, 0),
+            ],
+            doubled_weight: Score(-9, -12),
+            isolated_weight: Score(-13, -13),
+            backward_weight: Score(-8, -12),
+            island_weight: Score(-4, -8),
+            candidate_weight: Score(8, 20),
+            connected_passer_weight: Score(10, 20),
+            phalanx_weight: Score(6, 4),
+            pawn_center_weight: Score(6, 0),
+
+            castle_bonus: Score(-4, 0),
+
+            king_attack_weights: [
+                Score(0, 0),
+                Score(0, 0),
+                Score(2, 2),
+                Score(2, 2),
+                Score(5, 5),
+                Score(0, 0),
+                Score(3, 3),
+                Score(3, 3),
+            ],
+            safe_check_weights: [
+                Score(0, 0),
+                Score(0, 0),
+                Score(3, 3),
+                Score(2, 2),
+                Score(3, 3),
+                Score(0, 0),
+                Score(3, 3),
+                Score(3, 3),
+            ],
+            king_safety_table: [
+                Score(0, 0),
+                Score(0, 0),
+                Score(-3, -1),
+                Score(-6, -2),
+                Score(-12, -4),
+                Score(-18, -6),
+                Score(-27, -9),
+                Score(-36, -12),
+                Score(-48, -16),
+                Score(-60, -20),
+                Score(-75, -25),
+                Score(-90, -30),
+                Score(-108, -36),
+                Score(-126, -42),
+                Score(-147, -49),
+                Score(-168, -56),
+                Score(-192, -64),
+                Score(-216, -72),
+                Score(-243, -81),
+                Score(-270, -90),
+                Score(-300, -100),
+                Score(-330, -110),
+                Score(-363, -121),
+                Score(-396, -132),
+                Score(-432, -144),
+                Score(-468, -156),
+                Score(-507, -169),
+                Score(-546, -182),
+                Score(-588, -196),
+                Score(-630, -210),
+                Score(-675, -225),
+                Score(-720, -240),
+            ],
+            shelter_weights: [
+                Score(-30, 0),
+                Score(30, 0),
+                Score(20, 0),
+                Score(8, 0),
+                Score(2, 0),
+                Score(0, 0),
+                Score(0, 0),
+                Score(0, 0),
+            ],
+            storm_weights: [
+                Score(0, 0),
+                Score(-10, 0),
+                Score(-30, -5),
+                Score(-15, -5),
+                Score(-5, 0),
+                Score(0, 0),
+                Score(0, 0),
+                Score(0, 0),
+            ],
+
+            knight_move_weight: Score(5, 5),
+            bishop_move_weight: Score(6, 6),
+            rook_move_weight: Score(3, 3),
+            queen_move_weight: Score(1, 1),
+            king_move_weight: Score(-5, 3),
+
+            bishop_pair_weight: Score(30, 50),
+            rook_open_weight: Score(25, 10),
+            rook_semi_open_weight: Score(12, 6),
+            rook_seventh_weight: Score(10, 20),
+            knight_outpost_weight: Score(20, 10),
+            bishop_outpost_weight: Score(12, 6),
+            bad_bishop_weight: Score(-2, -4),
+            trapped_bishop_weight: Score(-100, -80),
+            trapped_rook_weight: Score(-40, -10),
+
+            hanging_weight: Score(30, 20),
+            pawn_threat_weight: Score(60, 40),
+            minor_threat_weight: Score(25, 30),
+            rook_threat_weight: Score(30, 30),
+            pawn_push_threat_weight: Score(20, 15),
+            weak_queen_weight: Score(25, 8),
+
+            free_passer_weights: [
+                Score(0, 0),
+                Score(0, 0),
+                Score(2, 5),
+                Score(5, 10),
+                Score(10, 20),
+                Score(15, 35),
+                Score(25, 60),
+                Score(0, 0),
             ],
+            passer_support_weight: Score(5, 15),
+            passer_own_king_weight: Score(0, -4),
+            passer_enemy_king_weight: Score(0, 8),
+            rook_behind_passer_weight: Score(10, 25),
+            unstoppable_passer_weight: Score(0, 500),
+
+            pawn_weight: Score(100, 100),
+            knight_weight: Score(279, 279),
+            bishop_weight: Score(293, 293),
+            rook_weight: Score(466, 466),
+            queen_weight: Score(866, 866),
+            king_weight: Score(25600, 25600),
+            imbalance_ours: [
+                // pawns
+                Score(0, 0),
+                // knights gain with pawns on the board
+                Score(16, 16),
+                Score(-8, -8),
+                // bishops
+                Score(0, 0),
+                Score(0, 0),
+                Score(0, 0),
+                // rooks lose with them, and a second rook adds less
+                Score(-16, -16),
+                Score(0, 0),
+                Score(0, 0),
+                Score(-64, -64),
+                // a queen and rooks get in each other's way
+                Score(0, 0),
+                Score(0, 0),
+                Score(0, 0),
+                Score(-96, -96),
+                Score(-64, -64),
+            ],
+            imbalance_theirs: [
+                Score(0, 0),
+                Score(0, 0),
+                Score(0, 0),
+                Score(0, 0),
+                Score(0, 0),
+                Score(0, 0),
+                Score(0, 0),
+                Score(0, 0),
+                Score(0, 0),
+                Score(0, 0),
+                // a queen against rooks
+                Score(0, 0),
+                Score(0, 0),
+                Score(0, 0),
+                Score(-32, -32),
+                Score(0, 0),
+            ],
+
+            psts: tapered_psts(&MIDDLEGAME_PSTS, &KING_ENDGAME_PST),
+
+            check_weights: [Score(0, 0), Score(400, 400), Score(150, 150), Score(0, 0)],
+            hill_weights: [Score(0, 0), Score(120, 120), Score(50, 50), Score(15, 15)],
+            pocket_weights: [
+                Score(0, 0),
+                Score(120, 120),
+                Score(300, 300),
+                Score(310, 310),
+                Score(880, 880),
+                Score(0, 0),
+                Score(480, 480),
+                Score(0, 0),
+            ],
+            drop_danger_weight: Score(6, 6),
+
+            lazy_margin: Score(500, 700),
+
+            generation: 0,
         }
 
         // Self {
@@ -222,38 +892,261 @@ impl Default for EvalParams {
     }
 }
 
+impl EvalParams {
+    /// The weights for `variant`, which are `active` outside of antichess.
+    pub fn for_variant(variant: Variant, active: &EvalParams) -> &EvalParams {
+        if variant == Variant::Antichess {
+            &ANTICHESS_PARAMS
+        } else {
+            active
+        }
+    }
+
+    /// Weights for antichess, where material is a liability and the kings are
+    /// ordinary pieces.
+    pub fn antichess() -> Self {
+        EvalParams {
+            knight_move_weight: Score(4, 4),
+            bishop_move_weight: Score(3, 3),
+            rook_move_weight: Score(2, 2),
+            queen_move_weight: Score(1, 1),
+            king_move_weight: Score(3, 3),
+
+            pawn_weight: Score(-60, -60),
+            knight_weight: Score(-150, -150),
+            bishop_weight: Score(-120, -120),
+            rook_weight: Score(-210, -210),
+            queen_weight: Score(-280, -280),
+            king_weight: Score(-140, -140),
+
+            psts: [[Score(0, 0); 64]; 16],
+            generation: next_generation(),
+            ..EvalParams::default()
+        }
+    }
+}
+
+impl EvalParams {
+    /// The weights used outside of antichess: the ones last passed to
+    /// `set_active`, or the compiled-in defaults.
+    pub fn active() -> Arc<EvalParams> {
+        ACTIVE_PARAMS.get().unwrap()
+    }
+
+    pub fn set_active(params: EvalParams) {
+        ACTIVE_PARAMS.set(EvalParams {
+            generation: next_generation(),
+            ..params
+        });
+    }
+
+    /// Reads weights in the format written by `Display`.
+    pub fn load(path: &str) -> Result<Self, ()> {
+        fs::read_to_string(path).map_err(|_| ())?.parse()
+    }
+
+    // value of a piece code for SEE, negative for black pieces
+    fn piece_value(&self, piece: u8) -> i32 {
+        let value = match piece & 7 {
+            1 => self.pawn_weight,
+            2 => self.knight_weight,
+            3 => self.bishop_weight,
+            4 => self.queen_weight,
+            5 => self.king_weight,
+            6 | 7 => self.rook_weight,
+            _ => Score(0, 0),
+        };
+
+        invert_if(piece & 8 != 0, value.0)
+    }
+
+    // every named group of weights in the text format, the piece-square
+    // tables being listed from a8 to h1
+    fn fields_mut(&mut self) -> Vec<(String, &mut [Score])> {
+        macro_rules! single {
+            ($params:ident, $($name:ident),*) => {
+                vec![$((stringify!($name).to_string(), slice::from_mut(&mut $params.$name))),*]
+            };
+        }
+
+        let mut out = single!(
+            self,
+            chain_weight,
+            doubled_weight,
+            isolated_weight,
+            backward_weight,
+            island_weight,
+            candidate_weight,
+            connected_passer_weight,
+            phalanx_weight,
+            pawn_center_weight,
+            castle_bonus,
+            knight_move_weight,
+            bishop_move_weight,
+            rook_move_weight,
+            queen_move_weight,
+            king_move_weight,
+            bishop_pair_weight,
+            rook_open_weight,
+            rook_semi_open_weight,
+            rook_seventh_weight,
+            knight_outpost_weight,
+            bishop_outpost_weight,
+            bad_bishop_weight,
+            trapped_bishop_weight,
+            trapped_rook_weight,
+            hanging_weight,
+            pawn_threat_weight,
+            minor_threat_weight,
+            rook_threat_weight,
+            pawn_push_threat_weight,
+            weak_queen_weight,
+            passer_support_weight,
+            passer_own_king_weight,
+            passer_enemy_king_weight,
+            rook_behind_passer_weight,
+            unstoppable_passer_weight,
+            pawn_weight,
+            knight_weight,
+            bishop_weight,
+            rook_weight,
+            queen_weight,
+            king_weight,
+            drop_danger_weight,
+            lazy_margin
+        );
+
+        out.push(("imbalance_ours".into(), &mut self.imbalance_ours[..]));
+        out.push(("imbalance_theirs".into(), &mut self.imbalance_theirs[..]));
+        out.push(("passed_weights".into(), &mut self.passed_weights[..]));
+        out.push((
+            "free_passer_weights".into(),
+            &mut self.free_passer_weights[..],
+        ));
+        out.push((
+            "king_attack_weights".into(),
+            &mut self.king_attack_weights[..],
+        ));
+        out.push((
+            "safe_check_weights".into(),
+            &mut self.safe_check_weights[..],
+        ));
+        out.push(("king_safety_table".into(), &mut self.king_safety_table[..]));
+        out.push(("shelter_weights".into(), &mut self.shelter_weights[..]));
+        out.push(("storm_weights".into(), &mut self.storm_weights[..]));
+        out.push(("check_weights".into(), &mut self.check_weights[..]));
+        out.push(("hill_weights".into(), &mut self.hill_weights[..]));
+        out.push(("pocket_weights".into(), &mut self.pocket_weights[..]));
+
+        // the unused tables of uncastled rooks are left out
+        for (piece, pst) in self.psts.iter_mut().enumerate() {
+            if piece & 7 != 0 && piece & 7 != 7 {
+                out.push((
+                    format!("pst_{}", &FEN_PIECES[piece..piece + 1]),
+                    &mut pst[..],
+                ));
+            }
+        }
+
+        out
+    }
+}
+
+// one group of weights per line, with piece-square tables laid out like a
+// board
+// the same weights, whatever their generation
+impl PartialEq for EvalParams {
+    fn eq(&self, other: &Self) -> bool {
+        let (mut a, mut b) = (self.clone(), other.clone());
+        a.fields_mut() == b.fields_mut()
+    }
+}
+
+impl fmt::Display for EvalParams {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, scores) in self.clone().fields_mut() {
+            if name.starts_with("pst_") {
+                writeln!(f, "{}", name)?;
+
+                for row in scores.rchunks(8) {
+                    let row: Vec<_> = row.iter().rev().map(|s| format!("{:>9}", s)).collect();
+                    writeln!(f, "  {}", row.join(" "))?;
+                }
+            } else {
+                let scores: Vec<_> = scores.iter().map(|s| s.to_string()).collect();
+                writeln!(f, "{} {}", name, scores.join(" "))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// groups that are left out keep their default values, and anything after a
+// '#' is a comment
+impl FromStr for EvalParams {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut out = EvalParams::default();
+        let mut fields = out.fields_mut();
+
+        let mut tokens = s
+            .lines()
+            .flat_map(|line| line.split('#').next().unwrap().split_whitespace());
+
+        while let Some(name) = tokens.next() {
+            let (name, scores) = fields.iter_mut().find(|(n, _)| n == name).ok_or(())?;
+
+            if name.starts_with("pst_") {
+                for score in scores.iter_mut().rev() {
+                    *score = tokens.next().ok_or(())?.parse()?;
+                }
+            } else {
+                for score in scores.iter_mut() {
+                    *score = tokens.next().ok_or(())?.parse()?;
+                }
+            }
+        }
+
+        std::mem::drop(fields);
+        Ok(out)
+    }
+}
+
 lazy_static! {
     pub static ref PARAMS: EvalParams = EvalParams::default();
-    static ref PIECE_VALUE: [i32; 16] = [
-        0,
-        PARAMS.pawn_weight,
-        PARAMS.knight_weight,
-        PARAMS.bishop_weight,
-        PARAMS.queen_weight,
-        PARAMS.king_weight,
-        PARAMS.rook_weight,
-        PARAMS.rook_weight,
-        0,
-        -PARAMS.pawn_weight,
-        -PARAMS.knight_weight,
-        -PARAMS.bishop_weight,
-        -PARAMS.queen_weight,
-        -PARAMS.king_weight,
-        -PARAMS.rook_weight,
-        -PARAMS.rook_weight,
-    ];
+    pub static ref ANTICHESS_PARAMS: EvalParams = EvalParams::antichess();
+    static ref ACTIVE_PARAMS: Active<EvalParams> = Active::with(EvalParams::default());
+}
+
+// the default weights have generation 0
+static GENERATIONS: AtomicU64 = AtomicU64::new(1);
+
+fn next_generation() -> u64 {
+    GENERATIONS.fetch_add(1, Ordering::Relaxed)
 }
 
 // const CENTER: u64 = 0x00003C3C3C3C0000;
 const CENTER: u64 = 0x0000001818000000;
 const PAWN_CENTER: u64 = 0x0000003C3C000000;
+// the fourth to sixth ranks, for white
+const OUTPOST_RANKS: u64 = 0x0000FFFFFF000000;
+
+// the squares a side attacks, also by kind of attacker
+#[derive(Clone, Copy, Default)]
+struct Attacks {
+    all: u64,
+    minors: u64,
+    rooks: u64,
+}
 
 fn region_bonus(region: u64, moves: u64, weight: i32) -> i32 {
     (moves & region).count_ones() as i32 * weight * 7 / 5
         + (moves & !region).count_ones() as i32 * weight
 }
 
-pub fn invert_if(b: bool, n: i32) -> i32 {
+pub fn invert_if<T: Neg<Output = T>>(b: bool, n: T) -> T {
     if b {
         -n
     } else {
@@ -262,26 +1155,245 @@ pub fn invert_if(b: bool, n: i32) -> i32 {
 }
 
 impl Board {
-    fn eval_material_pawnless(&self, params: &EvalParams) -> i32 {
-        let mut out = 0;
+    /// How far the position is from an endgame, going by the non-pawn
+    /// material left, including pieces in hand.
+    pub fn phase(&self) -> i32 {
+        let minors = (self.knights() | self.bishops()).count_ones() as i32;
+        let rooks = self.rooks().count_ones() as i32;
+        let queens = self.queens().count_ones() as i32;
+        let mut out = minors + 2 * rooks + 4 * queens;
+
+        for pocket in &self.pockets {
+            out += (pocket[2] + pocket[3] + 2 * pocket[6] + 4 * pocket[4]) as i32;
+        }
 
-        out += self.white_bishops().count_ones() as i32 * params.bishop_weight;
-        out -= self.black_bishops().count_ones() as i32 * params.bishop_weight;
+        out.min(MAX_PHASE)
+    }
 
-        out += self.white_knights().count_ones() as i32 * params.knight_weight;
-        out -= self.black_knights().count_ones() as i32 * params.knight_weight;
+    fn eval_pieces<S: EvalSink>(&self, params: &EvalParams, sink: &mut S) {
+        let kinds = [
+            self.pawns(),
+            self.knights(),
+            self.bishops(),
+            self.rooks(),
+            self.queens(),
+        ];
+        let counts = [self.white(), self.black()]
+            .map(|occ| kinds.map(|pieces| (pieces & occ).count_ones() as i32));
+
+        for (black, occ) in [(false, self.white()), (true, self.black())] {
+            let count = |pieces: u64| (pieces & occ).count_ones() as i32;
+
+            let score = count(self.knights()) * params.knight_weight
+                + count(self.bishops()) * params.bishop_weight
+                + count(self.rooks()) * params.rook_weight
+                + count(self.queens()) * params.queen_weight;
+
+            sink.record(Term::Material, black, score);
+
+            // ========== Imbalance ==========
+            let (ours, theirs) = (counts[black as usize], counts[!black as usize]);
+            let mut imbalance = Score(0, 0);
+
+            for i in 0..5 {
+                for j in 0..=i {
+                    let k = i * (i + 1) / 2 + j;
+
+                    imbalance += (params.imbalance_ours[k] * ours[j]
+                        + params.imbalance_theirs[k] * theirs[j])
+                        * ours[i];
+                }
+            }
 
-        out += self.white_rooks().count_ones() as i32 * params.rook_weight;
-        out -= self.black_rooks().count_ones() as i32 * params.rook_weight;
+            sink.record(
+                Term::Imbalance,
+                black,
+                Score(imbalance.0 / 16, imbalance.1 / 16),
+            );
+        }
+    }
 
-        out += self.white_queens().count_ones() as i32 * params.queen_weight;
-        out -= self.black_queens().count_ones() as i32 * params.queen_weight;
+    fn eval_placement<S: EvalSink>(&self, pawns: &PawnEntry, params: &EvalParams, sink: &mut S) {
+        let (w, b) = (self.white_pawns(), self.black_pawns());
+
+        for black in [false, true] {
+            let (own, opp) = if black {
+                (self.black(), self.white())
+            } else {
+                (self.white(), self.black())
+            };
+            let (own_pawns, opp_pawns) = if black { (b, w) } else { (w, b) };
+            let (us, them) = (black as usize, !black as usize);
+            // squares are flipped to white's side with this
+            let flip = if black { 56 } else { 0 };
+            let rank = |r: usize| -> u64 { 0xff << ((8 * r) ^ flip) };
+
+            let bishops = self.bishops() & own;
+            let rooks = self.rooks() & own;
+
+            // ========== Bishop Pair ==========
+            if bishops.count_ones() >= 2 {
+                sink.record(Term::BishopPair, black, params.bishop_pair_weight);
+            }
 
-        out
+            // ========== Rook Files ==========
+            let semi_open = pawns.pawnless_files[us];
+            let open = semi_open & pawns.pawnless_files[them];
+            let mut files = Score(0, 0);
+
+            for sq in LocStack(rooks) {
+                let file = 1 << (sq % 8);
+
+                if open & file != 0 {
+                    files += params.rook_open_weight;
+                } else if semi_open & file != 0 {
+                    files += params.rook_semi_open_weight;
+                }
+            }
+
+            if opp_pawns & rank(6) != 0 || self.kings() & opp & rank(7) != 0 {
+                files += (rooks & rank(6)).count_ones() as i32 * params.rook_seventh_weight;
+            }
+
+            sink.record(Term::RookFiles, black, files);
+
+            // ========== Outposts ==========
+            let outpost_ranks = if black {
+                OUTPOST_RANKS >> 8
+            } else {
+                OUTPOST_RANKS
+            };
+            let outposts = pawns.attacks[us] & !pawns.spans[them] & outpost_ranks;
+
+            sink.record(
+                Term::Outposts,
+                black,
+                (self.knights() & own & outposts).count_ones() as i32
+                    * params.knight_outpost_weight
+                    + (bishops & outposts).count_ones() as i32 * params.bishop_outpost_weight,
+            );
+
+            // ========== Bad Bishops ==========
+            let mut bad = 0;
+
+            for sq in LocStack(bishops) {
+                let color = if LIGHT_SQUARES & 1 << sq != 0 {
+                    LIGHT_SQUARES
+                } else {
+                    !LIGHT_SQUARES
+                };
+
+                bad += (own_pawns & color).count_ones() as i32;
+            }
+
+            sink.record(Term::BadBishops, black, bad * params.bad_bishop_weight);
+
+            // ========== Trapped Pieces ==========
+            let mut trapped = Score(0, 0);
+
+            // bishops on a7 and h7 shut in by a pawn on b6 or g6
+            for (bishop, pawn) in [(55, 46), (48, 41)] {
+                if bishops & 1 << (bishop ^ flip) != 0 && opp_pawns & 1 << (pawn ^ flip) != 0 {
+                    trapped += params.trapped_bishop_weight;
+                }
+            }
+
+            // rooks that can't castle anymore, between the king and the
+            // corner it's walked towards
+            let king = (self.kings() & own).trailing_zeros() as usize;
+            let back_rank = rank(0);
+
+            if 1 << king & back_rank != 0 {
+                let file = king % 8;
+                let outside: u64 = match file {
+                    1 | 2 => (1 << file) - 1,
+                    4..=6 => !((2 << file) - 1) & 0xff,
+                    _ => 0,
+                };
+                let stuck = rooks & !self.castling_rooks() & outside << flip;
+
+                trapped += stuck.count_ones() as i32 * params.trapped_rook_weight;
+            }
+
+            sink.record(Term::TrappedPieces, black, trapped);
+        }
     }
 
-    pub fn eval_material(&self, params: &EvalParams) -> i32 {
-        let mut out = 0;
+    // the parts of the passed pawn eval that depend on the other pieces, which
+    // the pawn hash table can't hold
+    fn eval_passers<S: EvalSink>(&self, pawns: &PawnEntry, params: &EvalParams, sink: &mut S) {
+        let occ = self.occ();
+
+        for black in [false, true] {
+            let (own, opp) = if black {
+                (self.black(), self.white())
+            } else {
+                (self.white(), self.black())
+            };
+            let passed = pawns.passed[black as usize];
+            let flip = if black { 56 } else { 0 };
+
+            let own_king = (self.kings() & own).trailing_zeros() as usize;
+            let opp_king = (self.kings() & opp).trailing_zeros() as usize;
+            let pawn_ending = opp & !self.pawns() & !self.kings() == 0;
+            // the other side gets to move first
+            let tempo = (self.black != black) as i32;
+
+            let mut out = Score(0, 0);
+
+            for sq in LocStack(passed) {
+                let rank = (sq ^ flip) / 8;
+                let file: u64 = 0x0101010101010101 << (sq % 8);
+                let (ahead, behind) = if black {
+                    (file & ((1 << sq) - 1), file & !((2 << sq) - 1))
+                } else {
+                    (file & !((2 << sq) - 1), file & ((1 << sq) - 1))
+                };
+                let promotion = (sq % 8) ^ flip ^ 56;
+
+                if ahead & occ == 0 {
+                    out += params.free_passer_weights[rank];
+                }
+
+                if TABLES.king[own_king] & 1 << sq != 0 {
+                    out += params.passer_support_weight;
+                }
+
+                let advanced = rank as i32 - 1;
+                out += advanced * distance(own_king, promotion) * params.passer_own_king_weight;
+                out += advanced * distance(opp_king, promotion) * params.passer_enemy_king_weight;
+
+                // the closest piece behind the pawn is one of our rooks
+                let behind = behind & occ;
+
+                if behind != 0 {
+                    let closest = if black {
+                        behind.trailing_zeros() as usize
+                    } else {
+                        63 - behind.leading_zeros() as usize
+                    };
+
+                    if self.rooks() & own & 1 << closest != 0 {
+                        out += params.rook_behind_passer_weight;
+                    }
+                }
+
+                // the rule of the square, with a double step from the
+                // second rank
+                let moves = (7 - rank as i32).min(5);
+
+                if pawn_ending && ahead & occ == 0 && distance(opp_king, promotion) - tempo > moves
+                {
+                    out += params.unstoppable_passer_weight;
+                }
+            }
+
+            sink.record(Term::PasserSupport, black, out);
+        }
+    }
+
+    pub fn eval_material(&self, params: &EvalParams) -> Score {
+        let mut out = Score(0, 0);
 
         out += self.white_pawns().count_ones() as i32 * params.pawn_weight;
         out -= self.black_pawns().count_ones() as i32 * params.pawn_weight;
@@ -301,14 +1413,163 @@ impl Board {
         out
     }
 
-    pub fn eval_mvv_lva(&self, mov: &Board) -> i32 {
+    fn eval_threats<S: EvalSink>(
+        &self,
+        attacks: &[Attacks; 2],
+        pawns: &PawnEntry,
+        params: &EvalParams,
+        sink: &mut S,
+    ) {
+        let occ = self.occ();
+
+        for black in [false, true] {
+            let (own, opp) = if black {
+                (self.black(), self.white())
+            } else {
+                (self.white(), self.black())
+            };
+            let (ours, theirs) = (attacks[black as usize], attacks[!black as usize]);
+            let pawn_attacks = pawns.attacks[black as usize];
+            let pieces = opp & !self.pawns() & !self.kings();
+            let queens = self.queens() & opp;
+            let count = |squares: u64| squares.count_ones() as i32;
+
+            // ========== Hanging Pieces ==========
+            let mut score = params.hanging_weight * count(pieces & ours.all & !theirs.all);
+
+            // ========== Attacks by Lesser Pieces ==========
+            score += params.pawn_threat_weight * count(pieces & pawn_attacks);
+            score +=
+                params.minor_threat_weight * count((self.rooks() | queens) & opp & ours.minors);
+            score += params.rook_threat_weight * count(queens & ours.rooks);
+
+            // ========== Pawn Push Threats ==========
+            let own_pawns = self.pawns() & own;
+            let pushes = if black {
+                let single = own_pawns >> 8 & !occ;
+                single | (single & 0x0000FF0000000000) >> 8 & !occ
+            } else {
+                let single = own_pawns << 8 & !occ;
+                single | (single & 0x0000000000FF0000) << 8 & !occ
+            };
+            let safe = pushes & !pawns.attacks[!black as usize] & (ours.all | !theirs.all);
+            let threats = if black {
+                b_pawn_threats(safe)
+            } else {
+                w_pawn_threats(safe)
+            };
+            score += params.pawn_push_threat_weight * count(threats & pieces & !pawn_attacks);
+
+            // ========== Weak Queen ==========
+            for sq in LocStack(queens) {
+                let diags = gen_bishop_moves(sq, occ);
+                let lines = gen_rook_moves(sq, occ);
+                let xrays = gen_bishop_moves(sq, occ & !diags) & !diags & self.bishops()
+                    | gen_rook_moves(sq, occ & !lines) & !lines & self.rooks();
+
+                score += params.weak_queen_weight * count(xrays & own);
+            }
+
+            sink.record(Term::Threats, black, score);
+        }
+    }
+
+    /// How much of the endgame score `eg`, from white's point of view, the
+    /// side that is ahead can expect to keep, out of `SCALE_NORMAL`.
+    pub fn scale_factor(&self, eg: i32, params: &EvalParams) -> i32 {
+        if self.variant != Variant::Standard {
+            return SCALE_NORMAL;
+        }
+
+        let black = eg < 0;
+        let (strong, weak) = if black {
+            (self.black(), self.white())
+        } else {
+            (self.white(), self.black())
+        };
+        let bishops = self.bishops();
+        let pieces = |occ: u64| {
+            let count = |pieces: u64| (pieces & occ).count_ones() as i32;
+
+            count(self.knights()) * params.knight_weight.1
+                + count(bishops) * params.bishop_weight.1
+                + count(self.rooks()) * params.rook_weight.1
+                + count(self.queens()) * params.queen_weight.1
+        };
+        let (strong_pieces, weak_pieces) = (pieces(strong), pieces(weak));
+        let strong_pawns = self.pawns() & strong;
+
+        // without pawns, being a minor piece up is rarely enough
+        if strong_pawns == 0 && strong_pieces - weak_pieces <= params.bishop_weight.1 {
+            return if strong_pieces < params.rook_weight.1 {
+                0
+            } else if weak_pieces <= params.bishop_weight.1 {
+                4
+            } else {
+                14
+            };
+        }
+
+        // rook pawns with a bishop that can't cover the queening square hold
+        // no hope once the defending king gets to the corner
+        let file = if strong_pawns & !0x0101010101010101 == 0 {
+            Some(0)
+        } else if strong_pawns & !0x8080808080808080 == 0 {
+            Some(7)
+        } else {
+            None
+        };
+        if let Some(file) = file {
+            let queening = if black { file } else { 56 + file };
+            let wrong_bishop = (bishops & strong).count_ones() == 1
+                && strong_pieces == params.bishop_weight.1
+                && (bishops & strong & LIGHT_SQUARES == 0) == (LIGHT_SQUARES >> queening & 1 == 1);
+            let king = (self.kings() & weak).trailing_zeros() as usize;
+
+            if (strong_pieces == 0 || wrong_bishop) && distance(king, queening) <= 1 {
+                return 0;
+            }
+        }
+
+        // opposite colored bishops
+        let light = bishops & LIGHT_SQUARES;
+        if (bishops & strong).count_ones() == 1
+            && (bishops & weak).count_ones() == 1
+            && light.count_ones() == 1
+        {
+            let others = self.knights() | self.rooks() | self.queens();
+
+            return if others == 0 { 16 } else { 40 };
+        }
+
+        SCALE_NORMAL
+    }
+
+    // material and piece-square tables, from white's point of view
+    fn eval_psqt(&self, params: &EvalParams) -> Score {
+        let mut out = self.eval_material(params);
+
+        for sq in LocStack(self.occ()) {
+            let piece = self.get_square(sq as u8);
+            // uncastled rooks use the rook tables
+            let pst = if piece & 7 == 7 { piece - 1 } else { piece };
+
+            out += invert_if(piece & 8 != 0, params.psts[pst as usize][sq]);
+        }
+
+        out
+    }
+
+    pub fn eval_mvv_lva(&self, mov: &Board, params: &EvalParams) -> i32 {
         let mut board = self.clone();
         board.b &= u64x4::splat(!(self.b ^ mov.b).reduce_or());
-        board.eval_material(&PARAMS)
+        board.eval_material(params).taper(board.phase())
     }
 
-    fn square_value(&self, black: bool, sq: usize) -> i32 {
-        invert_if(black, PIECE_VALUE[self.get_square(sq as u8) as usize])
+    fn square_value(&self, black: bool, sq: usize, params: &EvalParams) -> i32 {
+        let value = params.piece_value(self.get_square(sq as u8));
+
+        invert_if(black, value)
     }
 
     fn get_least_valuable(&self, mut att_def: u64, black: bool) -> u64 {
@@ -338,7 +1599,11 @@ impl Board {
         out & !(out.overflowing_sub(1).0)
     }
 
-    pub fn eval_see(&self, mov: &Board) -> i32 {
+    pub fn eval_see(&self, mov: &Board, params: &EvalParams) -> i32 {
+        if self.variant == Variant::Atomic {
+            return self.eval_see_atomic(mov, params);
+        }
+
         let mov = self.get_move(mov, true);
         let xray = self.pawns() | self.bishops() | self.rooks() | self.queens();
 
@@ -353,12 +1618,13 @@ impl Board {
         let mut att_def = self.get_att_def(occ, mov.end());
         let mut from = 1u64 << from_sq;
 
-        gain[0] = self.square_value(!black, to_sq);
+        gain[0] = self.square_value(!black, to_sq, params);
 
         while from != 0 {
             d += 1;
 
-            gain[d] = self.square_value(black, from.trailing_zeros() as usize) - gain[d - 1];
+            gain[d] =
+                self.square_value(black, from.trailing_zeros() as usize, params) - gain[d - 1];
 
             if gain[d].max(-gain[d - 1]) < 0 {
                 break;
@@ -385,11 +1651,54 @@ impl Board {
         gain[0]
     }
 
-    fn eval_pawns(&self, p_hash: &mut TT, params: &EvalParams) -> i32 {
-        if let Some(s) = p_hash.read(self.hash) {
-            return s as i32;
+    // there are no exchanges in atomic, a capture is worth whatever it
+    // blows up
+    fn eval_see_atomic(&self, mov: &Board, params: &EvalParams) -> i32 {
+        let value = |board: &Board| {
+            LocStack(board.occ())
+                .map(|sq| params.piece_value(board.get_square(sq as u8)))
+                .sum::<i32>()
+        };
+
+        invert_if(self.black, value(mov) - value(self))
+    }
+
+    fn eval_variant<S: EvalSink>(&self, params: &EvalParams, sink: &mut S) {
+        let w_king = (self.kings() & self.white()).trailing_zeros() as usize;
+        let b_king = (self.kings() & self.black()).trailing_zeros() as usize;
+
+        for (black, king, opp_king) in [(false, w_king, b_king), (true, b_king, w_king)] {
+            let score = match self.variant {
+                Variant::Standard | Variant::Antichess | Variant::Atomic => continue,
+                Variant::ThreeCheck => {
+                    params.check_weights[self.checks[black as usize].min(3) as usize]
+                }
+                Variant::KingOfTheHill => {
+                    let d = |x: usize| 3usize.saturating_sub(x).max(x.saturating_sub(4));
+                    let dist = d(king % 8).max(d(king / 8)).min(3);
+
+                    params.hill_weights[dist]
+                }
+                Variant::Crazyhouse => {
+                    let pocket = &self.pockets[black as usize];
+                    let pieces = pocket.iter().sum::<u8>().min(4) as i32;
+                    let holes = (TABLES.king[opp_king] & !self.occ()).count_ones() as i32;
+
+                    pocket
+                        .iter()
+                        .zip(params.pocket_weights)
+                        .map(|(n, weight)| *n as i32 * weight)
+                        .sum::<Score>()
+                        + pieces * holes * params.drop_danger_weight
+                }
+            };
+
+            sink.record(Term::VariantBonus, black, score);
         }
+    }
 
+    // the pawn structure of a board holding nothing but pawns
+    fn eval_pawns<S: EvalSink>(&self, params: &EvalParams, sink: &mut S) -> PawnEntry {
         let w = self.white_pawns();
         let b = self.black_pawns();
 
@@ -399,8 +1708,8 @@ impl Board {
         let w_chains = (w_threats & w).count_ones() as i32;
         let b_chains = (b_threats & b).count_ones() as i32;
 
-        let w_passed = (w & !southfill(b | b_threats)).count_ones() as i32;
-        let b_passed = (b & !northfill(w | w_threats)).count_ones() as i32;
+        let w_passed = w & !southfill(b | b_threats);
+        let b_passed = b & !northfill(w | w_threats);
 
         let w_doubled = (northfill(w) << 8 & w).count_ones() as i32;
         let b_doubled = (southfill(b) >> 8 & b).count_ones() as i32;
@@ -410,95 +1719,332 @@ impl Board {
         let w_isolated = (w_files & !adjacent(w_files)).count_ones() as i32;
         let b_isolated = (b_files & !adjacent(b_files)).count_ones() as i32;
 
-        let mut out = (w.count_ones() as i32 - b.count_ones() as i32) * params.pawn_weight;
-
-        out += (w_chains - b_chains) * params.chain_weight;
-        out += (w_passed - b_passed) * params.passed_weight;
-        out += (w_doubled - b_doubled) * params.doubled_weight;
-        out += (w_isolated - b_isolated) * params.isolated_weight;
+        // black's pawns are found by turning the board around
+        let w_backward = backward_pawns(w, b);
+        let b_backward = backward_pawns(b.swap_bytes(), w.swap_bytes()).swap_bytes();
+        let w_candidates = candidate_passers(w, b);
+        let b_candidates = candidate_passers(b.swap_bytes(), w.swap_bytes()).swap_bytes();
+
+        let mut out = PawnEntry {
+            hash: self.hash,
+            score: Score(0, 0),
+            passed: [w_passed, b_passed],
+            attacks: [w_threats, b_threats],
+            spans: [w_pawn_threats(northfill(w)), b_pawn_threats(southfill(b))],
+            pawnless_files: [!w_files, !b_files],
+            shelter: [None; 2],
+        };
 
-        for sq in LocStack(w) {
-            out += params.psts[1][sq];
+        for (black, pawns, chains, passed, doubled, isolated) in [
+            (false, w, w_chains, w_passed, w_doubled, w_isolated),
+            (true, b, b_chains, b_passed, b_doubled, b_isolated),
+        ] {
+            let pst = &params.psts[(black as usize) << 3 | 1];
+            let flip = if black { 56 } else { 0 };
+            let count = |squares: u64| squares.count_ones() as i32;
+
+            let files = southfill(pawns) as u8;
+            let islands = count((files & !(files << 1)) as u64);
+            let backward = if black { b_backward } else { w_backward };
+            let candidates = if black { b_candidates } else { w_candidates };
+            let connected = passed & file_mask(adjacent(southfill(passed) as u8));
+            let phalanxes =
+                pawns & ((pawns & 0x7f7f7f7f7f7f7f7f) << 1 | (pawns & 0xfefefefefefefefe) >> 1);
+            let threats = if black { b_threats } else { w_threats };
+            let center = (pawns | threats) & PAWN_CENTER;
+
+            let passed = LocStack(passed)
+                .map(|sq| params.passed_weights[(sq ^ flip) / 8])
+                .sum();
+
+            for (term, score) in [
+                (
+                    Term::Material,
+                    pawns.count_ones() as i32 * params.pawn_weight,
+                ),
+                (Term::PawnChains, chains * params.chain_weight),
+                (Term::PassedPawns, passed),
+                (Term::DoubledPawns, doubled * params.doubled_weight),
+                (Term::IsolatedPawns, isolated * params.isolated_weight),
+                (
+                    Term::BackwardPawns,
+                    count(backward) * params.backward_weight,
+                ),
+                (Term::PawnIslands, islands * params.island_weight),
+                (
+                    Term::CandidatePassers,
+                    count(candidates) * params.candidate_weight,
+                ),
+                (
+                    Term::ConnectedPassers,
+                    count(connected) * params.connected_passer_weight,
+                ),
+                (Term::Phalanxes, count(phalanxes) * params.phalanx_weight),
+                (Term::PawnCenter, count(center) * params.pawn_center_weight),
+                (Term::PawnPst, LocStack(pawns).map(|sq| pst[sq]).sum()),
+            ] {
+                out.score.record(term, black, score);
+                sink.record(term, black, score);
+            }
         }
 
-        for sq in LocStack(b) {
-            out -= params.psts[9][sq];
+        out
+    }
+
+    // pawn shelter and storm in front of a king on `king`
+    fn eval_shelter(&self, black: bool, king: usize, params: &EvalParams) -> Score {
+        let (own_pawns, opp_pawns) = if black {
+            (self.black_pawns(), self.white_pawns())
+        } else {
+            (self.white_pawns(), self.black_pawns())
+        };
+
+        // the king's own rank and the ones in front of it
+        let front = if black {
+            u64::MAX >> (56 - king / 8 * 8)
+        } else {
+            u64::MAX << (king / 8 * 8)
+        };
+        let closest_rank = |pawns: u64| match pawns & front {
+            0 => 0,
+            pawns if black => 7 - (63 - pawns.leading_zeros() as usize) / 8,
+            pawns => pawns.trailing_zeros() as usize / 8,
+        };
+
+        let file = king % 8;
+        let mut out = Score(0, 0);
+
+        for f in file.saturating_sub(1)..=(file + 1).min(7) {
+            let file_mask = 0x0101010101010101 << f;
+
+            out += params.shelter_weights[closest_rank(own_pawns & file_mask)];
+            out += params.storm_weights[closest_rank(opp_pawns & file_mask)];
         }
 
-        p_hash.write(self.hash, out as u64);
         out
     }
 }
 
+/// What the eval knows about a pawn structure, with everything indexed by
+/// color.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PawnEntry {
+    hash: u64,
+    // the pawn terms summed up from white's point of view
+    score: Score,
+    pub passed: [u64; 2],
+    pub attacks: [u64; 2],
+    // every square the pawns could attack by advancing
+    pub spans: [u64; 2],
+    // one bit per file, set when the color has no pawns on it
+    pub pawnless_files: [u8; 2],
+    // the king square each shelter score was counted for
+    shelter: [Option<(usize, Score)>; 2],
+}
+
+impl PawnEntry {
+    // the shelter of a king on `king`, counted again whenever it moves
+    fn shelter(&mut self, pawns: &Board, black: bool, king: usize, params: &EvalParams) -> Score {
+        match self.shelter[black as usize] {
+            Some((sq, score)) if sq == king => score,
+            _ => {
+                let score = pawns.eval_shelter(black, king, params);
+
+                self.shelter[black as usize] = Some((king, score));
+                score
+            }
+        }
+    }
+}
+
+/// Pawn structures of positions seen before, by pawn hash. Each search
+/// thread keeps its own.
+#[derive(Clone, Debug)]
+pub struct PawnTable {
+    entries: Vec<Option<PawnEntry>>,
+    // the generation of the weights the entries were counted with
+    generation: u64,
+}
+
+impl PawnTable {
+    pub fn with_len(len: usize) -> Self {
+        PawnTable {
+            entries: vec![None; len.max(1)],
+            generation: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+    }
+
+    // the entry for the pawns of `board`, whose terms are passed to `sink`
+    fn entry<S: EvalSink>(
+        &mut self,
+        board: &Board,
+        params: &EvalParams,
+        sink: &mut S,
+    ) -> &mut PawnEntry {
+        // scores from other weights are of no use
+        if self.generation != params.generation {
+            self.generation = params.generation;
+            self.clear();
+        }
+
+        let pawns = board.all_pawns();
+        let len = self.entries.len();
+        let slot = &mut self.entries[pawns.hash as usize % len];
+
+        match slot {
+            // a trace needs the terms apart, which aren't kept
+            Some(entry) if entry.hash == pawns.hash && !S::TRACE => {
+                sink.record(Term::Material, false, entry.score);
+            }
+            _ => *slot = Some(pawns.eval_pawns(params, sink)),
+        }
+
+        slot.as_mut().unwrap()
+    }
+}
+
 impl MoveGenerator {
-    fn eval_king(&mut self, params: &EvalParams) -> i32 {
+    fn eval_king<S: EvalSink>(&mut self, pawns: &mut PawnEntry, params: &EvalParams, sink: &mut S) {
         let occ = self.board.occ();
+        let black = self.board.black;
 
         let kingloc = (self.board.kings() & self.cur_occ).trailing_zeros() as usize;
 
-        let mut diag_attacks = gen_bishop_moves(kingloc, occ).count_ones() as i32;
-        diag_attacks *=
-            ((self.board.queens() | self.board.bishops()) & self.opp_occ).count_ones() as i32;
-
-        let mut rook_attacks = gen_rook_moves(kingloc, occ).count_ones() as i32;
-
-        rook_attacks *=
-            ((self.board.queens() | self.board.rooks()) & self.opp_occ).count_ones() as i32;
+        // ========== Pawn Shelter ==========
+        let shelter = pawns.shelter(&self.board, black, kingloc, params);
+
+        // ========== King Attacks ==========
+        let zone = TABLES.king[kingloc] | 1 << kingloc;
+        let safe = !self.opp_occ & !TABLES.king[kingloc] & !pawns.attacks[black as usize];
+
+        let diag_checks = gen_bishop_moves(kingloc, occ) & safe;
+        let line_checks = gen_rook_moves(kingloc, occ) & safe;
+        let attackers = (self.board.knights()
+            | self.board.bishops()
+            | self.board.rooks()
+            | self.board.queens())
+            & self.opp_occ;
+
+        // the two halves are counted separately
+        let mut units = Score(0, 0);
+
+        for sq in LocStack(attackers) {
+            let piece = (self.board.get_square(sq as u8) & 7) as usize;
+            let (attacks, checks) = match piece {
+                2 => (TABLES.knight[sq], TABLES.knight[kingloc] & safe),
+                3 => (gen_bishop_moves(sq, occ), diag_checks),
+                4 => (
+                    gen_bishop_moves(sq, occ) | gen_rook_moves(sq, occ),
+                    diag_checks | line_checks,
+                ),
+                _ => (gen_rook_moves(sq, occ), line_checks),
+            };
+
+            if attacks & zone != 0 {
+                units += params.king_attack_weights[piece];
+            }
+            if attacks & checks != 0 {
+                units += params.safe_check_weights[piece];
+            }
+        }
 
-        let king_pawns =
-            (TABLES.king[kingloc] & self.board.pawns() & self.cur_occ).count_ones() as i32;
+        let table = &params.king_safety_table;
+        let lookup = |units: i32| table[units.clamp(0, table.len() as i32 - 1) as usize];
 
-        let castle_rank = if self.board.black { 56 } else { 0 };
+        let castle_rank = if black { 56 } else { 0 };
         let castle_bonus = if kingloc > castle_rank
             && (kingloc - castle_rank == 1 || kingloc - castle_rank == 5)
         {
             params.castle_bonus
         } else {
-            0
+            Score(0, 0)
         };
 
-        invert_if(
-            self.board.black,
-            params.king_pawn_weight * king_pawns + castle_bonus - diag_attacks - rook_attacks,
-        )
+        sink.record(Term::KingShelter, black, shelter);
+        sink.record(Term::Castling, black, castle_bonus);
+        sink.record(
+            Term::KingAttacks,
+            black,
+            Score(lookup(units.0).0, lookup(units.1).1),
+        );
     }
 
-    pub fn eval_with_params(&mut self, board: Board, p_hash: &mut TT, params: &EvalParams) -> i32 {
+    /// Passes every term of the eval of `board` to `sink`, unless the game is
+    /// over, in which case the final score is returned instead.
+    // `draw` is the score of a stalemate for the side to move
+    fn eval_terms<S: EvalSink>(
+        &mut self,
+        board: Board,
+        p_hash: &mut PawnTable,
+        params: &EvalParams,
+        draw: i32,
+        sink: &mut S,
+    ) -> Option<i32> {
+        if board.is_variant_loss() {
+            return Some(-CHECKMATE);
+        } else if board.variant == Variant::Antichess {
+            return Some(self.eval_antichess(board, params));
+        }
+
         let occ = board.occ();
-        let pawns = board.all_pawns();
-        let mut out = pawns.eval_pawns(p_hash, params);
+        let pawns = p_hash.entry(&board, params, sink);
+        let mut attacks = [Attacks::default(); 2];
 
-        for (black, mul) in [(true, -1), (false, 1)] {
+        for black in [true, false] {
             self.set_board(Board { black, ..board });
 
-            out += self.eval_king(params);
+            self.eval_king(pawns, params, sink);
 
             if board.black == black && !self.has_moves() {
                 if self.checks == 0 {
-                    return 0;
+                    return Some(draw);
                 } else {
-                    return mul * -CHECKMATE;
+                    return Some(-CHECKMATE);
                 }
             }
 
+            let psts = &params.psts[(black as usize) << 3..];
             let kingloc = (self.board.kings() & self.cur_occ).trailing_zeros() as usize;
 
             // ========== King Moves ==========
             let moves = TABLES.king[kingloc] & !self.cur_occ & !self.threatened;
-            out += mul * params.king_move_weight * moves.count_ones() as i32;
-            out += mul * params.psts[(black as usize) << 3 | 5][kingloc];
+            sink.record(
+                Term::KingMobility,
+                black,
+                params.king_move_weight * moves.count_ones() as i32,
+            );
+            sink.record(Term::KingPst, black, psts[5][kingloc]);
+
+            // squares worth moving to, other than those enemy pawns guard, own
+            // pieces counting as defended
+            let own_pawns = self.board.pawns() & self.cur_occ;
+            let blocked = own_pawns & if black { occ << 8 } else { occ >> 8 };
+            let area =
+                !pawns.attacks[!black as usize] & !blocked & !(self.board.kings() & self.cur_occ);
+
+            attacks[!black as usize].all = self.threatened;
+            let ours = &mut attacks[black as usize];
 
             // ========== Knight Moves ==========
             for sq in LocStack(self.board.knights() & self.cur_occ) {
-                let mut moves = TABLES.knight[sq] & !self.cur_occ;
+                let mut moves = TABLES.knight[sq];
+                ours.minors |= moves;
 
                 moves &= self.blocks;
                 moves &= self.pins[sq];
 
-                moves &= !self.cur_occ;
+                moves &= area;
 
-                out += mul * moves.count_ones() as i32 * params.knight_move_weight;
-                out += mul * params.psts[(black as usize) << 3 | 2][sq];
+                sink.record(
+                    Term::KnightMobility,
+                    black,
+                    moves.count_ones() as i32 * params.knight_move_weight,
+                );
+                sink.record(Term::KnightPst, black, psts[2][sq]);
             }
 
             let cur_diags = (self.board.bishops() | self.board.queens()) & self.cur_occ;
@@ -506,50 +2052,145 @@ impl MoveGenerator {
 
             // ========== Bishop Moves ==========
             for sq in LocStack(self.board.bishops() & self.cur_occ) {
-                let mut moves = gen_bishop_moves(sq, occ & !cur_diags) & !self.cur_occ;
+                let mut moves = gen_bishop_moves(sq, occ & !cur_diags);
+                ours.minors |= moves;
 
                 moves &= self.blocks;
                 moves &= self.pins[sq];
 
-                moves &= !self.cur_occ;
+                moves &= area;
 
-                out += mul * moves.count_ones() as i32 * params.bishop_move_weight;
-                out += mul * params.psts[(black as usize) << 3 | 3][sq];
+                sink.record(
+                    Term::BishopMobility,
+                    black,
+                    moves.count_ones() as i32 * params.bishop_move_weight,
+                );
+                sink.record(Term::BishopPst, black, psts[3][sq]);
             }
 
             // ========== Rook Moves ==========
             for sq in LocStack(self.board.rooks() & self.cur_occ) {
-                let mut moves = gen_rook_moves(sq, occ & !cur_rook) & !self.cur_occ;
+                let mut moves = gen_rook_moves(sq, occ & !cur_rook);
+                ours.rooks |= moves;
 
                 moves &= self.blocks;
                 moves &= self.pins[sq];
 
-                moves &= !self.cur_occ;
+                moves &= area;
 
-                out += mul * moves.count_ones() as i32 * params.rook_move_weight;
-                out += mul * params.psts[(black as usize) << 3 | 6][sq];
+                sink.record(
+                    Term::RookMobility,
+                    black,
+                    moves.count_ones() as i32 * params.rook_move_weight,
+                );
+                sink.record(Term::RookPst, black, psts[6][sq]);
             }
 
             // ========== Queen Moves ==========
             for sq in LocStack(self.board.queens() & self.cur_occ) {
-                let mut moves = gen_rook_moves(sq, occ & !cur_rook) & !self.cur_occ
-                    | gen_bishop_moves(sq, occ & !cur_diags) & !self.cur_occ;
+                let mut moves =
+                    gen_rook_moves(sq, occ & !cur_rook) | gen_bishop_moves(sq, occ & !cur_diags);
 
                 moves &= self.blocks;
                 moves &= self.pins[sq];
 
-                moves &= !self.cur_occ;
+                moves &= area;
 
-                out += mul * moves.count_ones() as i32 * params.queen_move_weight;
-                out += mul * params.psts[(black as usize) << 3 | 4][sq];
+                sink.record(
+                    Term::QueenMobility,
+                    black,
+                    moves.count_ones() as i32 * params.queen_move_weight,
+                );
+                sink.record(Term::QueenPst, black, psts[4][sq]);
             }
         }
 
-        invert_if(board.black, out + board.eval_material_pawnless(params))
+        board.eval_variant(params, sink);
+        board.eval_pieces(params, sink);
+        board.eval_placement(pawns, params, sink);
+        board.eval_passers(pawns, params, sink);
+        board.eval_threats(&attacks, pawns, params, sink);
+
+        None
     }
 
-    pub fn eval(&mut self, board: Board, p_hash: &mut TT) -> i32 {
-        self.eval_with_params(board, p_hash, &PARAMS)
+    /// With a window, positions whose material and piece-square tables alone
+    /// are further than `lazy_margin` outside of it get a rough score, which
+    /// is outside the window as well. Stalemates score `draw`.
+    pub fn eval_with_params(
+        &mut self,
+        board: Board,
+        p_hash: &mut PawnTable,
+        params: &EvalParams,
+        window: Option<(i32, i32)>,
+        draw: i32,
+    ) -> i32 {
+        let (black, phase) = (board.black, board.phase());
+
+        // variant terms and mates can be worth any amount
+        if let Some((alpha, beta)) = window {
+            if board.variant == Variant::Standard && !board.in_check() {
+                let rough = invert_if(black, board.eval_psqt(params).taper(phase));
+                let margin = params.lazy_margin.taper(phase);
+
+                // a stalemate is only found by the full eval
+                if rough - margin >= beta || rough + margin <= alpha {
+                    self.set_board(board.clone());
+
+                    if self.has_moves() {
+                        return if rough - margin >= beta {
+                            rough - margin
+                        } else {
+                            rough + margin
+                        };
+                    }
+                }
+            }
+        }
+
+        let mut total = Score(0, 0);
+
+        match self.eval_terms(board.clone(), p_hash, params, draw, &mut total) {
+            Some(score) => score,
+            None => {
+                total.1 = total.1 * board.scale_factor(total.1, params) / SCALE_NORMAL;
+                invert_if(black, total.taper(phase))
+            }
+        }
+    }
+
+    pub fn eval(&mut self, board: Board, p_hash: &mut PawnTable) -> i32 {
+        let active = EvalParams::active();
+        let params = EvalParams::for_variant(board.variant, &active);
+
+        self.eval_with_params(board, p_hash, params, None, 0)
+    }
+
+    /// Evaluates `board` the same way as `eval`, keeping the terms apart.
+    pub fn eval_trace(&mut self, board: Board) -> EvalTrace {
+        let active = EvalParams::active();
+        let params = EvalParams::for_variant(board.variant, &active);
+        let mut trace = EvalTrace {
+            phase: board.phase(),
+            black: board.black,
+            ..EvalTrace::default()
+        };
+
+        // the pawn hash table only has summed up scores
+        let mut p_hash = PawnTable::with_len(0);
+
+        trace.score = match self.eval_terms(board.clone(), &mut p_hash, params, 0, &mut trace) {
+            Some(score) => score,
+            None => {
+                let mut total = trace.total();
+
+                trace.scale = board.scale_factor(total.1, params);
+                total.1 = total.1 * trace.scale / SCALE_NORMAL;
+                invert_if(trace.black, total.taper(trace.phase))
+            }
+        };
+
+        trace
     }
 }
 
@@ -559,12 +2200,222 @@ use test::Bencher;
 #[test]
 fn t_eval_pawns() {
     let board = Board::from_fen("8/2pppppp/8/7P/P6P/1P5P/2P5/8 w - -");
-    let mut tt = TT::with_len(10);
+    let mut tt = PawnTable::with_len(10);
+    let params = EvalParams {
+        psts: [[Score(0, 0); 64]; 16],
+        ..PARAMS.clone()
+    };
+
+    // a passer on a4 against two connected ones on e7 and f7, two islands
+    // against a black phalanx of six, and candidates on b3 and d7
+    let expected = 2 * PARAMS.chain_weight
+        + 2 * PARAMS.doubled_weight
+        + PARAMS.isolated_weight
+        + PARAMS.passed_weights[3]
+        - 2 * PARAMS.passed_weights[1]
+        + PARAMS.island_weight
+        - 2 * PARAMS.connected_passer_weight
+        - 6 * PARAMS.phalanx_weight
+        + PARAMS.pawn_center_weight;
+
+    // the second time around it comes from the pawn hash table
+    for _ in 0..2 {
+        let mut score = Score(0, 0);
+        let entry = *tt.entry(&board, &params, &mut score);
+
+        assert_eq!(score, expected);
+        assert_eq!(entry.passed, [1 << 31, 0x000C_0000_0000_0000]);
+        assert_eq!(entry.pawnless_files, [0b0001_1110, 0b1100_0000]);
+    }
 
+    // new weights don't reuse the old entries
+    let doubled = EvalParams {
+        doubled_weight: 2 * params.doubled_weight,
+        generation: next_generation(),
+        ..params.clone()
+    };
+
+    let mut score = Score(0, 0);
+    tt.entry(&board, &doubled, &mut score);
+    assert_eq!(score, expected + 2 * PARAMS.doubled_weight);
+}
+
+#[test]
+fn t_pawn_features() {
+    let pawns = |fen: &str| {
+        let board = Board::from_fen(fen);
+        (board.white_pawns(), board.black_pawns())
+    };
+
+    // d3 can't get to d4, and c4 has gone too far to help
+    let (w, b) = pawns("8/8/8/4p3/2P5/3P4/8/8 w - -");
+    assert_eq!(backward_pawns(w, b), 1 << 20);
+    assert_eq!(backward_pawns(b.swap_bytes(), w.swap_bytes()), 0);
+
+    // b3 and c2 against c7, while the g pawn is outnumbered
+    let (w, b) = pawns("8/2p3p1/8/7P/8/1P5P/2P5/8 w - -");
+    assert_eq!(candidate_passers(w, b), 1 << 22);
+    assert_eq!(candidate_passers(b.swap_bytes(), w.swap_bytes()), 0);
+}
+
+#[test]
+fn t_params_text() {
+    for params in [&*PARAMS, &*ANTICHESS_PARAMS] {
+        assert_eq!(params.to_string().parse::<EvalParams>(), Ok(params.clone()));
+    }
+
+    // tables read like a board, from a8 to h1
+    let mut pst = String::from("pst_n # black knights\n");
+    for sq in 0..64 {
+        pst += &format!(" {}/-{}", 63 - sq, sq);
+    }
+
+    let params: EvalParams = format!("chain_weight 5\n{}", pst).parse().unwrap();
+    assert_eq!(params.chain_weight, Score(5, 5));
+    assert_eq!(params.psts[10][0], Score(0, -63));
+    assert_eq!(params.psts[10][63], Score(63, 0));
+    assert_eq!(params.passed_weights, PARAMS.passed_weights);
+
+    assert_eq!("chain_weight".parse::<EvalParams>(), Err(()));
+    assert_eq!("chain_weight 1/x".parse::<EvalParams>(), Err(()));
+    assert_eq!("pst_R 1 2 3".parse::<EvalParams>(), Err(()));
+    assert_eq!("pst_T".parse::<EvalParams>(), Err(()));
+}
+
+#[test]
+fn t_taper() {
+    assert_eq!(Score(100, 20).taper(MAX_PHASE), 100);
+    assert_eq!(Score(100, 20).taper(0), 20);
+    assert_eq!(Score(100, 20).taper(MAX_PHASE / 2), 60);
+
+    assert_eq!(Board::from_fen(START_FEN).phase(), MAX_PHASE);
+    assert_eq!(Board::from_fen("4k3/4p3/8/8/8/8/4P3/4K3 w - -").phase(), 0);
+    assert_eq!(
+        Board::from_fen("4k3/4p3/8/8/8/8/4P3/3QKR2 w - -").phase(),
+        6
+    );
+
+    // with only pawns left the king is scored by its endgame table
+    let mut generator = MoveGenerator::empty();
+    let mut tt = PawnTable::with_len(0);
+    let center = Board::from_fen("8/8/8/4k3/8/8/4P3/7K w - -");
+    let corner = Board::from_fen("7k/8/8/8/8/8/4P3/7K w - -");
+
+    assert!(generator.eval(center, &mut tt) < generator.eval(corner, &mut tt));
+}
+
+#[test]
+fn t_eval_trace() {
+    let mut generator = MoveGenerator::empty();
+
+    for fen in [
+        START_FEN,
+        "rn1qk2r/p1pnbppp/bp2p3/3pN3/2PP4/1P4P1/P2BPPBP/RN1QK2R w KQkq -",
+        "8/5k2/3p4/1p1Pp2p/pP2Pp1P/P4P1K/8/8 b - -",
+        "k7/8/8/1Q6/8/8/8/7K b - -",
+    ] {
+        let board = Board::from_fen(fen);
+        let trace = generator.eval_trace(board.clone());
+
+        assert_eq!(
+            trace.score,
+            generator.eval(board, &mut PawnTable::with_len(0)),
+            "{}",
+            fen
+        );
+    }
+
+    let trace = generator.eval_trace(Board::from_fen(START_FEN));
+    let [w, b] = trace.get(Term::Material);
+
+    assert_eq!(w, b);
     assert_eq!(
-        board.eval_pawns(&mut tt, &PARAMS),
-        2 * PARAMS.chain_weight + 2 * PARAMS.doubled_weight + PARAMS.isolated_weight
-            - PARAMS.passed_weight
+        w,
+        8 * PARAMS.pawn_weight
+            + 2 * (PARAMS.knight_weight + PARAMS.bishop_weight + PARAMS.rook_weight)
+            + PARAMS.queen_weight
+    );
+    assert_eq!(trace.score, trace.total().taper(MAX_PHASE));
+}
+
+// the white and black scores of `term` in the eval of `fen`
+#[cfg(test)]
+fn trace_term(fen: &str, term: Term) -> [Score; 2] {
+    MoveGenerator::empty()
+        .eval_trace(Board::from_fen(fen))
+        .get(term)
+}
+
+#[test]
+fn t_eval_placement() {
+    assert_eq!(
+        trace_term(START_FEN, Term::BishopPair)[0],
+        PARAMS.bishop_pair_weight
+    );
+
+    // open, semi-open, and open on the seventh with the king on the eighth
+    let rooks = [
+        ("4k3/8/8/8/8/8/8/R3K3 w - -", PARAMS.rook_open_weight),
+        ("4k3/p7/8/8/8/8/8/R3K3 w - -", PARAMS.rook_semi_open_weight),
+        (
+            "4k3/R7/8/8/8/8/8/4K3 w - -",
+            PARAMS.rook_open_weight + PARAMS.rook_seventh_weight,
+        ),
+    ];
+    for (fen, score) in rooks {
+        assert_eq!(trace_term(fen, Term::RookFiles)[0], score, "{}", fen);
+    }
+
+    // a black pawn on c7 can still drive the knight away
+    let outpost = "4k3/8/8/3N4/4P3/8/8/4K3 w - -";
+    assert_eq!(
+        trace_term(outpost, Term::Outposts)[0],
+        PARAMS.knight_outpost_weight
+    );
+    let no_outpost = "4k3/2p5/8/3N4/4P3/8/8/4K3 w - -";
+    assert_eq!(trace_term(no_outpost, Term::Outposts)[0], Score(0, 0));
+
+    let bad = "4k3/8/8/8/8/8/1P1P4/2B1K3 w - -";
+    assert_eq!(
+        trace_term(bad, Term::BadBishops)[0],
+        2 * PARAMS.bad_bishop_weight
+    );
+
+    let bishop = "4k3/B7/1p6/8/8/8/8/4K3 w - -";
+    assert_eq!(
+        trace_term(bishop, Term::TrappedPieces)[0],
+        PARAMS.trapped_bishop_weight
+    );
+    let rook = "4k3/8/8/8/8/8/8/5KR1 w - -";
+    assert_eq!(
+        trace_term(rook, Term::TrappedPieces)[0],
+        PARAMS.trapped_rook_weight
+    );
+    let castling = "4k3/8/8/8/8/8/8/5K1R w K -";
+    assert_eq!(trace_term(castling, Term::TrappedPieces)[0], Score(0, 0));
+}
+
+#[test]
+fn t_eval_passers() {
+    // the black king is in the square of the pawn only if it is to move
+    let [w, _] = trace_term("8/8/1k6/8/6P1/8/8/K7 w - -", Term::PasserSupport);
+    let expected = PARAMS.free_passer_weights[3]
+        + 2 * 7 * PARAMS.passer_own_king_weight
+        + 2 * 5 * PARAMS.passer_enemy_king_weight;
+    assert_eq!(w, expected + PARAMS.unstoppable_passer_weight);
+
+    let [w, _] = trace_term("8/8/1k6/8/6P1/8/8/K7 b - -", Term::PasserSupport);
+    assert_eq!(w, expected);
+
+    // a rook behind the pawn and the king next to it
+    let [_, b] = trace_term("2r5/8/8/8/8/2p5/3k4/K7 w - -", Term::PasserSupport);
+    assert_eq!(
+        b,
+        PARAMS.free_passer_weights[5]
+            + PARAMS.passer_support_weight
+            + 4 * PARAMS.passer_own_king_weight
+            + 4 * 2 * PARAMS.passer_enemy_king_weight
+            + PARAMS.rook_behind_passer_weight
     );
 }
 
@@ -574,37 +2425,208 @@ fn t_eval_see() {
     let board1 = Board::from_fen("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - -");
     let board2 = Board::from_fen("1k1r4/1pp4p/p7/4R3/8/P5P1/1PP4P/2K5 b - -");
 
-    assert_eq!(board1.eval_see(&board2), PARAMS.pawn_weight);
+    assert_eq!(board1.eval_see(&board2, &PARAMS), PARAMS.pawn_weight.0);
 
     let board1 = Board::from_fen("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - -");
     let board2 = Board::from_fen("1k1r3q/1ppn3p/p4b2/4N3/8/P5P1/1PP1R1BP/2K1Q3 b - -");
 
     assert_eq!(
-        board1.eval_see(&board2),
-        PARAMS.pawn_weight - PARAMS.knight_weight
+        board1.eval_see(&board2, &PARAMS),
+        PARAMS.pawn_weight.0 - PARAMS.knight_weight.0
+    );
+}
+
+#[test]
+fn t_eval_king() {
+    let king_terms = |fen: &str| {
+        (
+            trace_term(fen, Term::KingShelter)[0],
+            trace_term(fen, Term::KingAttacks)[0],
+        )
+    };
+
+    // three unmoved pawns in front of the king, and none at all
+    let (shelter, attacks) = king_terms("6k1/8/8/8/8/8/5PPP/6K1 w - -");
+    assert_eq!(
+        shelter,
+        3 * PARAMS.shelter_weights[1] + 3 * PARAMS.storm_weights[0]
+    );
+    assert_eq!(attacks, PARAMS.king_safety_table[0]);
+
+    let (shelter, _) = king_terms("6k1/8/8/8/8/8/8/6K1 w - -");
+    assert_eq!(
+        shelter,
+        3 * PARAMS.shelter_weights[0] + 3 * PARAMS.storm_weights[0]
+    );
+
+    // a queen bearing on the king zone with a safe check on e1
+    let (_, attacks) = king_terms("6k1/8/8/8/7q/8/6PP/6K1 w - -");
+    let units = PARAMS.king_attack_weights[4] + PARAMS.safe_check_weights[4];
+    assert_eq!(
+        attacks,
+        Score(
+            PARAMS.king_safety_table[units.0 as usize].0,
+            PARAMS.king_safety_table[units.1 as usize].1
+        )
+    );
+
+    // knight checks on f3 and h3, covered by a pawn or not
+    let (_, attacks) = king_terms("6k1/8/8/6n1/8/8/8/6K1 w - -");
+    let units = PARAMS.safe_check_weights[2];
+    assert_eq!(attacks.0, PARAMS.king_safety_table[units.0 as usize].0);
+
+    let (_, attacks) = king_terms("6k1/8/8/6n1/8/8/6P1/6K1 w - -");
+    assert_eq!(attacks, PARAMS.king_safety_table[0]);
+}
+
+#[test]
+fn t_eval_game_over() {
+    let mut generator = MoveGenerator::empty();
+    let mut tt = PawnTable::with_len(0);
+
+    // stalemates score what the caller makes of a draw
+    let board = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - -");
+    assert_eq!(generator.eval(board.clone(), &mut tt), 0);
+    assert_eq!(
+        generator.eval_with_params(board, &mut tt, &PARAMS, None, -25),
+        -25
+    );
+
+    // checkmates are bad for the side to move, whichever side that is
+    let board = Board::from_fen("7k/6Q1/6K1/8/8/8/8/8 b - -");
+    assert_eq!(generator.eval(board, &mut tt), -CHECKMATE);
+    let board = Board::from_fen("8/8/8/8/8/6k1/6q1/7K w - -");
+    assert_eq!(generator.eval(board, &mut tt), -CHECKMATE);
+}
+
+#[test]
+fn t_eval_imbalance() {
+    let mut generator = MoveGenerator::empty();
+    let sixteenths = |s: Score| Score(s.0 / 16, s.1 / 16);
+
+    // two rooks against a queen
+    let trace = generator.eval_trace(Board::from_fen("3qk3/8/8/8/8/8/8/R2RK3 w - -"));
+    let [w, b] = trace.get(Term::Imbalance);
+    assert_eq!(w, sixteenths(PARAMS.imbalance_ours[9] * 4));
+    assert_eq!(
+        b,
+        sixteenths(PARAMS.imbalance_ours[14] + PARAMS.imbalance_theirs[13] * 2)
+    );
+
+    // a knight with all the pawns
+    let trace = generator.eval_trace(Board::from_fen("4k3/pppppppp/8/8/8/8/PPPPPPPP/1N2K3 w - -"));
+    let [w, b] = trace.get(Term::Imbalance);
+    let pawns = PARAMS.imbalance_ours[0] * 64 + PARAMS.imbalance_theirs[0] * 64;
+    assert_eq!(
+        w,
+        sixteenths(
+            pawns
+                + PARAMS.imbalance_ours[1] * 8
+                + PARAMS.imbalance_ours[2]
+                + PARAMS.imbalance_theirs[1] * 8
+        )
     );
+    assert_eq!(b, sixteenths(pawns));
 }
 
-// #[test]
-// fn t_eval_king() {
-// let mut generator = MoveGenerator::new(Board::from_fen("3rrqrr/8/8/8/8/8/5PPP/6K1 w - -"));
+#[test]
+fn t_eval_threats() {
+    // a knight hanging to a pawn
+    let [w, b] = trace_term("4k3/8/3p4/4N3/8/8/8/4K3 w - -", Term::Threats);
+    assert_eq!(
+        (w, b),
+        (
+            Score(0, 0),
+            PARAMS.hanging_weight + PARAMS.pawn_threat_weight
+        )
+    );
+
+    // e4-e5 hits the knight on f6
+    let [w, b] = trace_term("4k3/8/5n2/8/4P3/8/8/4K3 w - -", Term::Threats);
+    assert_eq!((w, b), (PARAMS.pawn_push_threat_weight, Score(0, 0)));
 
-// assert_eq!(generator.eval_king(), PARAMS.king_pawn_weight * 3 - 2 - 40)
-// }
+    // the queen behind the knight on the d file
+    let [w, b] = trace_term("3rk3/8/8/8/3N4/8/8/3QK3 b - -", Term::Threats);
+    assert_eq!((w, b), (Score(0, 0), PARAMS.weak_queen_weight));
+
+    // the knight can't count squares guarded by pawns, its king or the
+    // blocked pawn on f2
+    let knight = "4k3/8/8/2p1p3/8/3N1B2/5P2/4K3 w - -";
+    assert_eq!(
+        trace_term(knight, Term::KnightMobility)[0],
+        PARAMS.knight_move_weight * 4
+    );
+}
+
+#[test]
+fn t_scale_factor() {
+    let scale = |fen: &str, eg: i32| Board::from_fen(fen).scale_factor(eg, &PARAMS);
+
+    // opposite colored bishops, alone and with rooks
+    assert_eq!(scale("4k3/5p2/8/2b5/3P4/8/4BP2/4K3 w - -", 100), 16);
+    assert_eq!(scale("r3k3/5p2/8/2b5/3P4/8/4BP2/R3K3 w - -", 100), 40);
+    assert_eq!(scale("4k3/5p2/2b5/8/3P4/8/4BP2/4K3 w - -", 100), 64);
+
+    // rook against bishop, and a lone knight for black
+    assert_eq!(scale("4k3/8/8/2b5/8/8/8/R3K3 w - -", 100), 4);
+    assert_eq!(scale("4k3/8/8/2n5/8/8/8/4K3 w - -", -100), 0);
+
+    // a light squared bishop doesn't cover h8, so only the h pawn matters
+    assert_eq!(scale("7k/8/8/7P/8/8/4B3/4K3 w - -", 100), 0);
+    assert_eq!(scale("7k/8/8/7P/8/8/3B4/4K3 w - -", 100), 64);
+    assert_eq!(scale("8/4k3/8/7P/8/8/4B3/4K3 w - -", 100), 64);
+}
+
+#[test]
+fn t_lazy_eval() {
+    let mut generator = MoveGenerator::empty();
+    let mut tt = PawnTable::with_len(0);
+    // a queen up
+    let board = Board::from_fen("4k3/pppp4/8/8/8/8/PPPP4/3QK3 w - -");
+    let full = generator.eval(board.clone(), &mut tt);
+
+    // inside the window the eval is exact
+    let window = Some((full - 1, full + 1));
+    assert_eq!(
+        generator.eval_with_params(board.clone(), &mut tt, &PARAMS, window, 0),
+        full
+    );
+
+    // far below it the rough score still fails low, far above it fails high
+    let mut lazy = |window| generator.eval_with_params(board.clone(), &mut tt, &PARAMS, window, 0);
+    assert!(lazy(Some((-3000, -2000))) >= -2000);
+    assert!(lazy(Some((3000, 4000))) <= 3000);
+
+    // positions in check are never cut short
+    let board = Board::from_fen("4k3/pppp4/8/8/8/8/PPPP4/3QK2r w - -");
+    let full = generator.eval(board.clone(), &mut tt);
+    assert_eq!(
+        generator.eval_with_params(board, &mut tt, &PARAMS, Some((3000, 4000)), 0),
+        full
+    );
+
+    // nor are stalemates
+    let board = Board::from_fen("k7/2Q5/1K6/8/8/8/8/8 b - -");
+    assert_eq!(generator.eval(board.clone(), &mut tt), 0);
+    assert_eq!(
+        generator.eval_with_params(board, &mut tt, &PARAMS, Some((-10, 10)), 0),
+        0
+    );
+}
 
 #[bench]
 fn b_eval_see(b: &mut Bencher) {
     let board1 = Board::from_fen("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - -");
     let board2 = Board::from_fen("1k1r4/1pp4p/p7/4R3/8/P5P1/1PP4P/2K5 b - -");
 
-    b.iter(|| test::black_box(&board1).eval_see(&board2))
+    b.iter(|| test::black_box(&board1).eval_see(&board2, &PARAMS))
 }
 
 #[bench]
 fn b_eval(b: &mut Bencher) {
     let mut generator = MoveGenerator::empty();
     let board = Board::from_fen("rn1qk2r/p1pnbppp/bp2p3/3pN3/2PP4/1P4P1/P2BPPBP/RN1QK2R w KQkq -");
-    let mut tt = TT::with_len(0);
+    let mut tt = PawnTable::with_len(0);
 
     b.iter(|| generator.eval(board.clone(), &mut tt));
 }
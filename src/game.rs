@@ -0,0 +1,277 @@
+use crate::board::*;
+use crate::gen_moves::*;
+use crate::moves::*;
+use crate::types::*;
+use crate::variant::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Checkmate(Color),
+    VariantWin(Color),
+    Stalemate,
+    ThreefoldRepetition,
+    FiftyMoveRule,
+    InsufficientMaterial,
+}
+
+impl Outcome {
+    pub fn winner(&self) -> Option<Color> {
+        match self {
+            Outcome::Checkmate(c) | Outcome::VariantWin(c) => Some(*c),
+            _ => None,
+        }
+    }
+}
+
+/// A game played out from a starting position. Keeps every position reached
+/// and its halfmove clock so that draws by repetition and the fifty-move rule
+/// can be detected.
+#[derive(Clone, Debug)]
+pub struct Game {
+    boards: Vec<Board>,
+    moves: Vec<Move>,
+    clocks: Vec<u16>,
+    // the fullmove number of the starting position
+    fullmove: u16,
+}
+
+impl Game {
+    pub fn new(start: Board) -> Self {
+        Self::with_clock(start, 0)
+    }
+
+    pub fn with_clock(start: Board, halfmove_clock: u16) -> Self {
+        Self {
+            boards: vec![start],
+            moves: Vec::new(),
+            clocks: vec![halfmove_clock],
+            fullmove: 1,
+        }
+    }
+
+    /// Reads the position, halfmove clock and fullmove number of a FEN
+    /// string.
+    pub fn from_fen(fen: &str) -> Self {
+        let mut fields = fen
+            .split_whitespace()
+            .filter(|w| parse_checks(w).is_none())
+            .skip(4);
+        let clock = fields.next().and_then(|w| w.parse().ok()).unwrap_or(0);
+        let fullmove = fields.next().and_then(|w| w.parse().ok()).unwrap_or(1);
+
+        Self {
+            fullmove: fullmove.max(1),
+            ..Self::with_clock(Board::from_fen(fen), clock)
+        }
+    }
+
+    /// Replays the game under a different variant.
+    pub fn with_variant(self, variant: Variant) -> Self {
+        let start = self.start().clone().with_variant(variant);
+        let mut out = Self {
+            fullmove: self.fullmove,
+            ..Self::with_clock(start, self.clocks[0])
+        };
+
+        for mov in self.moves {
+            out.push(mov);
+        }
+
+        out
+    }
+
+    pub fn start(&self) -> &Board {
+        &self.boards[0]
+    }
+
+    pub fn board(&self) -> &Board {
+        self.boards.last().unwrap()
+    }
+
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    pub fn halfmove_clock(&self) -> u16 {
+        *self.clocks.last().unwrap()
+    }
+
+    /// The fullmove number of the current position, counting on from the
+    /// starting one.
+    pub fn fullmove_number(&self) -> usize {
+        self.fullmove as usize + (self.moves.len() + self.start().black as usize) / 2
+    }
+
+    pub fn push(&mut self, mov: Move) {
+        let board = self.board().do_move(mov);
+
+        self.push_board(mov, board);
+    }
+
+    /// Plays `mov` with `board` as the position it leads to, for callers
+    /// that have already made the move.
+    pub fn push_board(&mut self, mov: Move, board: Board) {
+        let clock = if self.board().is_irreversible(&board) {
+            0
+        } else {
+            self.halfmove_clock() + 1
+        };
+
+        self.boards.push(board);
+        self.clocks.push(clock);
+        self.moves.push(mov);
+    }
+
+    pub fn pop(&mut self) -> Option<Move> {
+        let mov = self.moves.pop()?;
+
+        self.boards.pop();
+        self.clocks.pop();
+
+        Some(mov)
+    }
+
+    /// Number of times the current position has occurred, including now.
+    pub fn repetitions(&self) -> usize {
+        let hash = self.board().hash;
+
+        // nothing before the last capture or pawn move can come back
+        self.boards
+            .iter()
+            .rev()
+            .take(self.halfmove_clock() as usize + 1)
+            .step_by(2)
+            .filter(|b| b.hash == hash)
+            .count()
+    }
+
+    pub fn outcome(&self) -> Option<Outcome> {
+        if self.board().is_variant_loss() {
+            return Some(Outcome::VariantWin(!self.board().side_to_move()));
+        }
+
+        let mut generator = MoveGenerator::new(self.board().clone());
+
+        if !generator.has_moves() {
+            if self.board().variant == Antichess {
+                return Some(Outcome::VariantWin(self.board().side_to_move()));
+            } else if generator.get_checks() == 0 {
+                return Some(Outcome::Stalemate);
+            } else {
+                return Some(Outcome::Checkmate(!self.board().side_to_move()));
+            }
+        }
+
+        if self.halfmove_clock() >= 100 {
+            Some(Outcome::FiftyMoveRule)
+        } else if self.repetitions() >= 3 {
+            Some(Outcome::ThreefoldRepetition)
+        } else if self.board().variant == Standard && self.board().is_insufficient_material() {
+            Some(Outcome::InsufficientMaterial)
+        } else {
+            None
+        }
+    }
+}
+
+impl Board {
+    /// Whether `next` is reached by a capture or pawn move, resetting the
+    /// fifty-move counter.
+    pub fn is_irreversible(&self, next: &Board) -> bool {
+        let (cur_occ, opp_occ, next_opp_occ) = if self.black {
+            (self.black(), self.white(), next.white())
+        } else {
+            (self.white(), self.black(), next.black())
+        };
+
+        self.pawns() & cur_occ & !next.pawns() != 0 || opp_occ & !next_opp_occ != 0
+    }
+
+    /// Whether neither side has enough material left to deliver mate.
+    pub fn is_insufficient_material(&self) -> bool {
+        if self.pawns() | self.rooks() | self.queens() != 0 {
+            return false;
+        }
+
+        let bishops = self.bishops();
+
+        if self.knights() != 0 {
+            return (self.knights() | bishops).count_ones() <= 1;
+        }
+
+        bishops & LIGHT_SQUARES == 0 || bishops & !LIGHT_SQUARES == 0
+    }
+}
+
+#[cfg(test)]
+fn play(fen: &str, moves: &str) -> Game {
+    let mut game = Game::from_fen(fen);
+
+    for mov in moves.split_whitespace() {
+        game.push(mov.parse().unwrap());
+    }
+
+    game
+}
+
+#[test]
+fn t_outcome() {
+    let game = play(START_FEN, "f2f3 e7e5 g2g4 d8h4");
+    assert_eq!(game.outcome(), Some(Outcome::Checkmate(Color::Black)));
+    assert_eq!(game.outcome().unwrap().winner(), Some(Color::Black));
+
+    let game = play("k7/8/8/1Q6/8/8/8/7K w - - 0 1", "h1g1");
+    assert_eq!(game.outcome(), None);
+    let game = play("k7/8/8/1Q6/8/8/8/7K w - - 0 1", "b5b6");
+    assert_eq!(game.outcome(), Some(Outcome::Stalemate));
+
+    let mut game = play(START_FEN, "g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1");
+    assert_eq!(game.outcome(), None);
+    game.push("f6g8".parse().unwrap());
+    assert_eq!(game.repetitions(), 3);
+    assert_eq!(game.outcome(), Some(Outcome::ThreefoldRepetition));
+
+    let mut game = play("4k3/8/8/8/8/8/4P3/R3K3 w - - 98 80", "a1a2");
+    assert_eq!(game.outcome(), None);
+    game.push("e8d8".parse().unwrap());
+    assert_eq!(game.outcome(), Some(Outcome::FiftyMoveRule));
+    game.pop();
+    game.pop();
+    game.push("e2e4".parse().unwrap());
+    assert_eq!(game.halfmove_clock(), 0);
+
+    let game = play("4k3/8/8/8/8/8/8/4KB2 w - - 0 1", "");
+    assert_eq!(game.outcome(), Some(Outcome::InsufficientMaterial));
+}
+
+#[test]
+fn t_fullmove_number() {
+    assert_eq!(play(START_FEN, "").fullmove_number(), 1);
+    assert_eq!(play(START_FEN, "e2e4 e7e5 g1f3").fullmove_number(), 2);
+
+    // counting goes on from the FEN, where black may be the first to move
+    let fen = "4k3/8/8/8/8/8/4P3/4K3 b - - 3 40";
+    assert_eq!(play(fen, "").fullmove_number(), 40);
+    assert_eq!(play(fen, "e8d8").fullmove_number(), 41);
+    assert_eq!(play(fen, "e8d8 e1d1 d8e8").fullmove_number(), 42);
+}
+
+#[test]
+fn t_insufficient_material() {
+    for (fen, res) in [
+        ("4k3/8/8/8/8/8/8/4K3 w - -", true),
+        ("4k3/8/8/8/8/8/8/4KN2 w - -", true),
+        ("4kb2/8/8/8/8/8/8/2B1K3 w - -", true),
+        ("2b1k3/8/8/8/8/8/8/2B1K3 w - -", false),
+        ("4kn2/8/8/8/8/8/8/4KN2 w - -", false),
+        ("4k3/8/8/8/8/8/8/3NKN2 w - -", false),
+        ("4k3/8/8/8/8/8/4P3/4K3 w - -", false),
+    ] {
+        assert_eq!(
+            Board::from_fen(fen).is_insufficient_material(),
+            res,
+            "{}",
+            fen
+        );
+    }
+}
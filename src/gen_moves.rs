@@ -1,11 +1,22 @@
 #[path = "eval.rs"]
 pub mod eval;
 
+#[path = "antichess.rs"]
+mod antichess;
+
+#[path = "atomic.rs"]
+mod atomic;
+
+#[path = "nnue.rs"]
+pub mod nnue;
+
 use crate::board::*;
 use crate::gen_tables::*;
+use crate::variant::*;
 
+#[cfg(not(feature = "pext"))]
 #[inline]
-fn gen_rook_moves(sq: usize, mut occ: u64) -> u64 {
+pub fn gen_rook_moves(sq: usize, mut occ: u64) -> u64 {
     let (mask, magic, offset) = TABLES.rook[sq];
 
     occ &= mask;
@@ -16,8 +27,9 @@ fn gen_rook_moves(sq: usize, mut occ: u64) -> u64 {
     TABLES.magic[occ as usize]
 }
 
+#[cfg(not(feature = "pext"))]
 #[inline]
-fn gen_bishop_moves(sq: usize, mut occ: u64) -> u64 {
+pub fn gen_bishop_moves(sq: usize, mut occ: u64) -> u64 {
     let (mask, magic, offset) = TABLES.bishop[sq];
 
     occ &= mask;
@@ -28,6 +40,22 @@ fn gen_bishop_moves(sq: usize, mut occ: u64) -> u64 {
     TABLES.magic[occ as usize]
 }
 
+#[cfg(feature = "pext")]
+#[inline]
+pub fn gen_rook_moves(sq: usize, occ: u64) -> u64 {
+    let (mask, offset) = TABLES.pext_rook[sq];
+
+    TABLES.pext[offset + pext(occ, mask) as usize]
+}
+
+#[cfg(feature = "pext")]
+#[inline]
+pub fn gen_bishop_moves(sq: usize, occ: u64) -> u64 {
+    let (mask, offset) = TABLES.pext_bishop[sq];
+
+    TABLES.pext[offset + pext(occ, mask) as usize]
+}
+
 impl Board {
     pub fn get_att_def(&self, occ: u64, sq: usize) -> u64 {
         let mut out = 0;
@@ -68,6 +96,10 @@ impl Board {
     }
 
     pub fn get_checks(&self) -> u64 {
+        if self.variant == Variant::Antichess {
+            return 0;
+        }
+
         let cur_occ = if self.black {
             self.black()
         } else {
@@ -76,6 +108,13 @@ impl Board {
 
         let kingloc = (self.kings() & cur_occ).trailing_zeros() as usize;
 
+        // a king that is gone or touching the enemy king can't be captured
+        if kingloc >= 64
+            || self.variant == Variant::Atomic && TABLES.king[kingloc] & self.kings() != 0
+        {
+            return 0;
+        }
+
         self.get_threats(kingloc)
     }
 
@@ -84,6 +123,9 @@ impl Board {
     }
 }
 
+// pawns can't be dropped on the first or last rank
+const PAWN_DROP_RANKS: u64 = 0xff000000000000ff;
+
 #[derive(Debug, PartialEq)]
 pub struct MoveGenerator {
     board: Board,
@@ -134,9 +176,28 @@ impl MoveGenerator {
             self.cur_pawn_takes = &TABLES.white_pawn_takes;
             self.opp_pawn_takes = &TABLES.black_pawn_takes;
         }
-        self.set_threatened();
-        self.set_pins();
-        self.set_blocks();
+
+        match self.board.variant {
+            Variant::Antichess => {
+                // kings are ordinary pieces, so there are no checks or pins
+                self.pins.fill(u64::MAX);
+                self.threatened = 0;
+                self.checks = 0;
+                self.blocks = u64::MAX;
+            }
+            Variant::Atomic => {
+                // moves are checked for legality after they are made
+                self.set_threatened();
+                self.pins.fill(u64::MAX);
+                self.checks = self.board.get_checks();
+                self.blocks = u64::MAX;
+            }
+            _ => {
+                self.set_threatened();
+                self.set_pins();
+                self.set_blocks();
+            }
+        }
     }
 
     fn get_threats_board(&self, board: &Board, sq: usize) -> u64 {
@@ -284,8 +345,165 @@ fn do_moves(out: &mut Vec<Board>, board: &Board, sq: usize, moves: u64) {
     }
 }
 
+// promotion pieces in the order they are reached by the xors in
+// `push_promotions`: queen, rook, bishop, knight, king
+const PROMOTIONS: [[u64; 4]; 5] = [
+    [0, 1, 0, 0],
+    [0, 0, 1, 0],
+    [0, 1, 0, 1],
+    [0, 0, 0, 1],
+    [0, 1, 1, 1],
+];
+
+fn push_promotions(out: &mut Vec<Board>, board: &Board, black: bool, sq: u64, promotions: usize) {
+    let mut board2 = board.clone();
+    board2.b &= u64x4::splat(!(1 << sq));
+    board2.b |= u64x4::from_array([black as u64, 0, 0, 0]) << sq;
+
+    for diff in &PROMOTIONS[..promotions] {
+        board2.b ^= u64x4::from_array(*diff) << sq;
+
+        let mut board3 = board2.clone();
+        board3.update_hash(board);
+        out.push(board3);
+    }
+}
+
 impl MoveGenerator {
+    /// Generates pseudo-legal moves to `targets` for variants where the king
+    /// can be left in check, without castling. Pawn pushes are only generated
+    /// with `quiet_pawns`, and pawns can promote to kings with
+    /// `king_promotions`.
+    fn gen_pseudo_legal(&mut self, targets: u64, quiet_pawns: bool, king_promotions: bool) {
+        self.moves.clear();
+
+        let promotions = 4 + king_promotions as usize;
+        let occ = self.board.occ();
+        let (pawn_shift, double_mask, promote_mask): (_, u64, u64) = if self.board.black {
+            (
+                Box::new(|x| x >> 8) as Box<dyn Fn(u64) -> u64>,
+                0x00ff000000000000,
+                0x000000000000ff00,
+            )
+        } else {
+            (
+                Box::new(|x| x << 8) as Box<dyn Fn(u64) -> u64>,
+                0x000000000000ff00,
+                0x00ff000000000000,
+            )
+        };
+
+        let mut board = self.board.clone();
+        board.black ^= true;
+        board.remove_takeable_empty();
+        board.update_hash(&self.board);
+
+        // ========== Pawn Moves ==========
+        for sq in LocStack(self.board.pawns() & self.cur_occ) {
+            let mut moves = self.cur_pawn_takes[sq] & self.opp_occ;
+
+            if quiet_pawns {
+                moves |= pawn_shift(1 << sq) & !occ;
+            }
+
+            if promote_mask & 1 << sq == 0 {
+                do_moves(&mut self.moves, &board, sq, moves);
+            } else {
+                let mut board2 = board.clone();
+                board2.b &= u64x4::splat(!(1 << sq));
+                board2.update_hash(&board);
+
+                for sq2 in LocStack(moves) {
+                    push_promotions(
+                        &mut self.moves,
+                        &board2,
+                        self.board.black,
+                        sq2 as u64,
+                        promotions,
+                    );
+                }
+            }
+
+            if !quiet_pawns || double_mask & 1 << sq == 0 {
+                continue;
+            }
+
+            for sq2 in LocStack(pawn_shift(pawn_shift(1 << sq) & !occ) & !occ) {
+                let mut board2 = board.clone();
+                board2.b |= u64x4::from_array([pawn_shift(1 << sq), 0, 0, 0]);
+                board2.b &= u64x4::splat(!(1 << sq));
+                board2.b |= u64x4::from_array([self.board.black as u64, 0, 0, 1]) << (sq2 as u64);
+                board2.update_hash(&board);
+
+                self.moves.push(board2);
+            }
+        }
+
+        // ========== En Passant ==========
+        for te in LocStack(self.board.takeable_empties()) {
+            for sq in LocStack(self.opp_pawn_takes[te] & self.board.pawns() & self.cur_occ) {
+                let mut board2 = board.clone();
+                board2.b ^= TABLES.en_pass[self.board.black as usize][(te % 8 > sq % 8) as usize]
+                    << (sq as u64 % 8);
+                board2.update_hash(&board);
+
+                self.moves.push(board2);
+            }
+        }
+
+        // ========== Knight Moves ==========
+        for sq in LocStack(self.board.knights() & self.cur_occ) {
+            do_moves(&mut self.moves, &board, sq, TABLES.knight[sq] & targets);
+        }
+
+        // ========== Bishop Moves ==========
+        for sq in LocStack(self.board.bishops() & self.cur_occ) {
+            do_moves(
+                &mut self.moves,
+                &board,
+                sq,
+                gen_bishop_moves(sq, occ) & targets,
+            );
+        }
+
+        // ========== Rook Moves ==========
+        for sq in LocStack(self.board.rooks() & self.cur_occ) {
+            let mut board2 = board.clone();
+            board2.b &= u64x4::from_array([M, M, M, !(1 << sq)]);
+            board2.update_hash(&board);
+
+            do_moves(
+                &mut self.moves,
+                &board2,
+                sq,
+                gen_rook_moves(sq, occ) & targets,
+            );
+        }
+
+        // ========== Queen Moves ==========
+        for sq in LocStack(self.board.queens() & self.cur_occ) {
+            let moves = gen_bishop_moves(sq, occ) | gen_rook_moves(sq, occ);
+
+            do_moves(&mut self.moves, &board, sq, moves & targets);
+        }
+
+        // ========== King Moves ==========
+        let mut board2 = board.clone();
+        board2.b ^= u64x4::from_array([0, 0, 0, board.castling_rooks() & self.cur_occ]);
+        board2.update_hash(&board);
+
+        for sq in LocStack(self.board.kings() & self.cur_occ) {
+            do_moves(&mut self.moves, &board2, sq, TABLES.king[sq] & targets);
+        }
+    }
+
     pub fn has_moves(&mut self) -> bool {
+        match self.board.variant {
+            Variant::Antichess => return self.has_antichess_moves(),
+            Variant::Atomic => return self.has_atomic_moves(),
+            _ => {}
+        }
+
         let occ = self.board.occ();
 
         let pawn_shift = if self.board.black {
@@ -307,6 +525,18 @@ impl MoveGenerator {
             return false;
         }
 
+        // ========== Drops ==========
+        if self.board.variant == Variant::Crazyhouse {
+            let pocket = &self.board.pockets[self.board.black as usize];
+            let empty = !occ & self.blocks;
+
+            if pocket[1] > 0 && empty & !PAWN_DROP_RANKS != 0
+                || pocket[2..].iter().any(|n| *n > 0) && empty != 0
+            {
+                return true;
+            }
+        }
+
         // ========== Pawn Moves ==========
         for sq in LocStack(self.board.pawns() & self.cur_occ) {
             let mut moves = pawn_shift(1 << sq) & !occ;
@@ -399,6 +629,36 @@ impl MoveGenerator {
     }
 
     pub fn gen_moves(&mut self) {
+        match self.board.variant {
+            Variant::Antichess => self.gen_antichess_moves(false),
+            Variant::Atomic => self.gen_atomic_moves(false),
+            _ => {
+                self.gen_legal_moves();
+                self.update_variant_moves();
+            }
+        }
+    }
+
+    pub fn gen_tactical(&mut self) {
+        match self.board.variant {
+            Variant::Antichess => self.gen_antichess_moves(true),
+            Variant::Atomic => self.gen_atomic_moves(true),
+            _ => {
+                self.gen_legal_tactical();
+                self.update_variant_moves();
+            }
+        }
+    }
+
+    fn update_variant_moves(&mut self) {
+        if self.board.variant != Variant::Standard {
+            for board in &mut self.moves {
+                board.update_variant(&self.board);
+            }
+        }
+    }
+
+    fn gen_legal_moves(&mut self) {
         self.moves.clear();
 
         let occ = self.board.occ();
@@ -592,9 +852,36 @@ impl MoveGenerator {
 
             do_moves(&mut self.moves, &board, sq, moves);
         }
+
+        // ========== Drops ==========
+        if self.board.variant == Variant::Crazyhouse {
+            let pocket = self.board.pockets[self.board.black as usize];
+            let empty = !occ & self.blocks;
+
+            for (code, n) in pocket.iter().enumerate() {
+                if *n == 0 {
+                    continue;
+                }
+
+                let piece = piece_to_sq(code as u8 | (self.board.black as u8) << 3);
+                let moves = if code == 1 {
+                    empty & !PAWN_DROP_RANKS
+                } else {
+                    empty
+                };
+
+                for sq in LocStack(moves) {
+                    let mut board2 = board.clone();
+                    board2.b |= piece << (sq as u64);
+                    board2.update_hash(&board);
+
+                    self.moves.push(board2);
+                }
+            }
+        }
     }
 
-    pub fn gen_tactical(&mut self) {
+    fn gen_legal_tactical(&mut self) {
         self.moves.clear();
 
         let occ = self.board.occ();
@@ -1,3 +1,4 @@
+use rand::{thread_rng, Rng};
 use std::simd::u64x4;
 
 pub const M: u64 = u64::MAX;
@@ -319,11 +320,7 @@ pub fn piece_to_sq(piece: u8) -> u64x4 {
 }
 
 pub fn abs_diff(a: usize, b: usize) -> usize {
-    if a > b {
-        a - b
-    } else {
-        b - a
-    }
+    a.abs_diff(b)
 }
 
 fn gen_ep_table() -> Vec<Vec<u64x4>> {
@@ -416,33 +413,155 @@ fn gen_sliding_table(bishop: bool) -> Vec<(u64, u64, u64)> {
     out
 }
 
-fn gen_magic_table() -> Vec<u64> {
-    let mut out = vec![0; 97264];
+// lookups shift by a fixed amount, giving every square 2^9 (bishop) or 2^12
+// (rook) entries, which may overlap between squares
+fn magic_shift(bishop: bool) -> u32 {
+    if bishop {
+        55
+    } else {
+        52
+    }
+}
+
+// writes the attacks of every occupancy of `sq` into `table`, failing if two
+// occupancies with different attacks land on the same entry
+fn fill_magic(table: &mut [u64], sq: usize, bishop: bool, magic: u64, offset: u64) -> bool {
+    for (occ, att) in gen_occ_att(sq, bishop) {
+        let i = (occ.wrapping_mul(magic) >> magic_shift(bishop)) + offset;
+
+        match table.get_mut(i as usize) {
+            Some(entry) if *entry == 0 => *entry = att,
+            Some(entry) if *entry == att => {}
+            _ => return false,
+        }
+    }
+
+    true
+}
+
+/// Builds the shared lookup table for the given (magic, offset) pairs of each
+/// square, or `None` if they don't fit in `len` entries without collisions.
+pub fn build_magic_table(
+    bishop: &[(u64, u64)],
+    rook: &[(u64, u64)],
+    len: usize,
+) -> Option<Vec<u64>> {
+    let mut out = vec![0; len];
+
     for sq in 0..64 {
-        for (mut occ, att) in gen_occ_att(sq, true) {
-            occ = occ.overflowing_mul(BISHOP_MAGICS[sq]).0;
-            occ >>= 55;
-            occ += BISHOP_OFFSETS[sq];
-            if out[occ as usize] == 0 {
-                out[occ as usize] = att;
-            } else if out[occ as usize] != att {
-                panic!("Invalid magics!");
-            }
+        let (magic, offset) = bishop[sq];
+
+        if !fill_magic(&mut out, sq, true, magic, offset) {
+            return None;
         }
 
-        for (mut occ, att) in gen_occ_att(sq, false) {
-            occ = occ.overflowing_mul(ROOK_MAGICS[sq]).0;
-            occ >>= 52;
-            occ += ROOK_OFFSETS[sq];
-            if out[occ as usize] == 0 {
-                out[occ as usize] = att;
-            } else if out[occ as usize] != att {
-                panic!("Invalid magics!");
-            }
+        let (magic, offset) = rook[sq];
+
+        if !fill_magic(&mut out, sq, false, magic, offset) {
+            return None;
         }
     }
 
-    out
+    Some(out)
+}
+
+/// Searches for a magic for `sq` that maps every occupancy of its mask to an
+/// index without destructive collisions.
+pub fn find_magic<R: Rng>(sq: usize, bishop: bool, rng: &mut R) -> u64 {
+    let mask = gen_mask(sq, &deltas(bishop));
+    let mut table = vec![0; 1 << (64 - magic_shift(bishop))];
+
+    loop {
+        // sparse candidates are much more likely to work
+        let magic = rng.gen::<u64>() & rng.gen::<u64>() & rng.gen::<u64>();
+
+        if (mask.wrapping_mul(magic) >> 56).count_ones() < 6 {
+            continue;
+        }
+
+        table.fill(0);
+
+        if fill_magic(&mut table, sq, bishop, magic, 0) {
+            return magic;
+        }
+    }
+}
+
+/// Finds magics for every square, with each square getting its own slice of
+/// the table after the ones before it.
+pub fn find_magics<R: Rng>(bishop: bool, start: u64, rng: &mut R) -> Vec<(u64, u64)> {
+    let size = 1 << (64 - magic_shift(bishop));
+
+    (0..64)
+        .map(|sq| (find_magic(sq, bishop, rng), start + sq as u64 * size))
+        .collect()
+}
+
+/// Prints a freshly found set of magics in the format of the constants at
+/// the top of this file.
+pub fn print_magics() {
+    let mut rng = thread_rng();
+    let bishop = find_magics(true, 0, &mut rng);
+    let rook = find_magics(false, 64 << 9, &mut rng);
+
+    for (name, magics) in [("BISHOP", &bishop), ("ROOK", &rook)] {
+        println!("const {}_MAGICS: [u64; 64] = [", name);
+
+        for (magic, _) in magics.iter() {
+            println!("    {:#018x},", magic);
+        }
+
+        println!("];\n");
+        println!("const {}_OFFSETS: [u64; 64] = [", name);
+
+        for (_, offset) in magics.iter() {
+            println!("    {},", offset);
+        }
+
+        println!("];\n");
+    }
+}
+
+fn gen_magic_table() -> Vec<u64> {
+    let bishop: Vec<_> = BISHOP_MAGICS.iter().copied().zip(BISHOP_OFFSETS).collect();
+    let rook: Vec<_> = ROOK_MAGICS.iter().copied().zip(ROOK_OFFSETS).collect();
+
+    build_magic_table(&bishop, &rook, MAGIC_LEN).expect("Invalid magics!")
+}
+
+// with pext the index into a square's slice of the table is just the
+// occupancy bits under its mask, packed together
+#[cfg(feature = "pext")]
+type PextMasks = Vec<(u64, usize)>;
+
+#[cfg(feature = "pext")]
+fn gen_pext_table() -> (PextMasks, PextMasks, Vec<u64>) {
+    let mut out = Vec::new();
+    let mut slider = |bishop: bool| {
+        (0..64)
+            .map(|sq| {
+                let offset = out.len();
+
+                // gen_occ_att enumerates occupancies in pext order
+                out.extend(gen_occ_att(sq, bishop).into_iter().map(|(_, att)| att));
+                (gen_mask(sq, &deltas(bishop)), offset)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let bishop = slider(true);
+    let rook = slider(false);
+
+    (bishop, rook, out)
+}
+
+#[cfg(all(feature = "pext", not(target_feature = "bmi2")))]
+compile_error!("the pext feature needs BMI2, build with RUSTFLAGS=\"-C target-feature=+bmi2\"");
+
+#[cfg(feature = "pext")]
+#[inline]
+pub fn pext(a: u64, mask: u64) -> u64 {
+    unsafe { std::arch::x86_64::_pext_u64(a, mask) }
 }
 
 fn gen_move_table(deltas: &[(isize, isize)]) -> Vec<u64> {
@@ -467,48 +586,491 @@ fn gen_move_table(deltas: &[(isize, isize)]) -> Vec<u64> {
     out
 }
 
+// Everything below builds the tables at compile time, so they have to stick
+// to what const fns allow: while loops, no iterators or closures, and arrays
+// instead of u64x4 until the very end.
+
+const KNIGHT_DELTAS: [(isize, isize); 8] = [
+    (1, 2),
+    (-1, 2),
+    (1, -2),
+    (-1, -2),
+    (2, 1),
+    (-2, 1),
+    (2, -1),
+    (-2, -1),
+];
+
+const KING_DELTAS: [(isize, isize); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+const BISHOP_DELTAS: [(isize, isize); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const ROOK_DELTAS: [(isize, isize); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+// every square's slice of the table ends at most 2^9 (bishop) or 2^12
+// (rook) entries after its offset
+const fn magic_len() -> usize {
+    let mut out = 0;
+    let mut sq = 0;
+
+    while sq < 64 {
+        let bishop = BISHOP_OFFSETS[sq] as usize + (1 << 9);
+        let rook = ROOK_OFFSETS[sq] as usize + (1 << 12);
+
+        if bishop > out {
+            out = bishop;
+        }
+        if rook > out {
+            out = rook;
+        }
+        sq += 1;
+    }
+
+    out
+}
+
+pub const MAGIC_LEN: usize = magic_len();
+
+const fn on_board(x: isize, y: isize) -> bool {
+    0 <= x && x < 8 && 0 <= y && y < 8
+}
+
+const fn move_table(deltas: &[(isize, isize)]) -> [u64; 64] {
+    let mut out = [0; 64];
+    let mut sq = 0;
+
+    while sq < 64 {
+        let mut i = 0;
+
+        while i < deltas.len() {
+            let x = (sq % 8) as isize + deltas[i].0;
+            let y = (sq / 8) as isize + deltas[i].1;
+
+            if on_board(x, y) {
+                out[sq] |= 1 << (x + y * 8);
+            }
+            i += 1;
+        }
+        sq += 1;
+    }
+
+    out
+}
+
+// squares seen along `delta` from `sq` on an empty board, stopping before
+// the edge unless `edges`
+const fn ray(sq: usize, (dx, dy): (isize, isize), edges: bool) -> u64 {
+    let mut out = 0;
+    let mut x = (sq % 8) as isize + dx;
+    let mut y = (sq / 8) as isize + dy;
+
+    while on_board(x, y) && (edges || on_board(x + dx, y + dy)) {
+        out |= 1 << (x + y * 8);
+        x += dx;
+        y += dy;
+    }
+
+    out
+}
+
+const fn rays(deltas: &[(isize, isize); 4]) -> [[u64; 64]; 4] {
+    let mut out = [[0; 64]; 4];
+    let mut i = 0;
+
+    while i < 4 {
+        let mut sq = 0;
+
+        while sq < 64 {
+            out[i][sq] = ray(sq, deltas[i], true);
+            sq += 1;
+        }
+        i += 1;
+    }
+
+    out
+}
+
+const BISHOP_RAYS: [[u64; 64]; 4] = rays(&BISHOP_DELTAS);
+const ROOK_RAYS: [[u64; 64]; 4] = rays(&ROOK_DELTAS);
+
+// cuts each ray off behind its first blocker. The deltas alternate between
+// going up and down the board, so that's the lowest blocker on even rays and
+// the highest on odd ones. Walking the rays square by square is too slow to
+// run for every occupancy in const eval.
+const fn slider_att(sq: usize, rays: &[[u64; 64]; 4], occ: u64) -> u64 {
+    let mut out = 0;
+    let mut i = 0;
+
+    while i < 4 {
+        let ray = rays[i][sq];
+        let blockers = ray & occ;
+
+        if blockers == 0 {
+            out |= ray;
+        } else if i % 2 == 0 {
+            out |= ray & !rays[i][blockers.trailing_zeros() as usize];
+        } else {
+            out |= ray & !rays[i][63 - blockers.leading_zeros() as usize];
+        }
+        i += 1;
+    }
+
+    out
+}
+
+const fn slider_masks(deltas: &[(isize, isize); 4]) -> [u64; 64] {
+    let mut out = [0; 64];
+    let mut sq = 0;
+
+    while sq < 64 {
+        let mut i = 0;
+
+        while i < 4 {
+            out[sq] |= ray(sq, deltas[i], false);
+            i += 1;
+        }
+        sq += 1;
+    }
+
+    out
+}
+
+const fn sliding_table(bishop: bool) -> [(u64, u64, u64); 64] {
+    let (masks, magics, offsets) = if bishop {
+        (slider_masks(&BISHOP_DELTAS), BISHOP_MAGICS, BISHOP_OFFSETS)
+    } else {
+        (slider_masks(&ROOK_DELTAS), ROOK_MAGICS, ROOK_OFFSETS)
+    };
+    let mut out = [(0, 0, 0); 64];
+    let mut sq = 0;
+
+    while sq < 64 {
+        out[sq] = (masks[sq], magics[sq], offsets[sq]);
+        sq += 1;
+    }
+
+    out
+}
+
+const BISHOP_TABLE: [(u64, u64, u64); 64] = sliding_table(true);
+const ROOK_TABLE: [(u64, u64, u64); 64] = sliding_table(false);
+
+#[cfg(not(feature = "pext"))]
+const fn magic_table() -> [u64; MAGIC_LEN] {
+    let mut out = [0; MAGIC_LEN];
+    let mut sq = 0;
+
+    while sq < 64 {
+        let mut j = 0;
+
+        while j < 2 {
+            let ((mask, magic, offset), rays, shift) = if j == 0 {
+                (BISHOP_TABLE[sq], &BISHOP_RAYS, 55)
+            } else {
+                (ROOK_TABLE[sq], &ROOK_RAYS, 52)
+            };
+
+            // walks through every subset of the mask
+            let mut occ: u64 = 0;
+
+            loop {
+                let i = (occ.wrapping_mul(magic) >> shift) + offset;
+                out[i as usize] = slider_att(sq, rays, occ);
+
+                occ = occ.wrapping_sub(mask) & mask;
+
+                if occ == 0 {
+                    break;
+                }
+            }
+            j += 1;
+        }
+        sq += 1;
+    }
+
+    out
+}
+
+#[cfg(feature = "pext")]
+const fn pext_masks(deltas: &[(isize, isize); 4], start: usize) -> [(u64, usize); 64] {
+    let masks = slider_masks(deltas);
+    let mut out = [(0, 0); 64];
+    let mut offset = start;
+    let mut sq = 0;
+
+    while sq < 64 {
+        out[sq] = (masks[sq], offset);
+        offset += 1 << masks[sq].count_ones();
+        sq += 1;
+    }
+
+    out
+}
+
+#[cfg(feature = "pext")]
+const fn pext_end(masks: &[(u64, usize); 64]) -> usize {
+    masks[63].1 + (1 << masks[63].0.count_ones())
+}
+
+#[cfg(feature = "pext")]
+const PEXT_BISHOP: [(u64, usize); 64] = pext_masks(&BISHOP_DELTAS, 0);
+#[cfg(feature = "pext")]
+const PEXT_ROOK: [(u64, usize); 64] = pext_masks(&ROOK_DELTAS, pext_end(&PEXT_BISHOP));
+#[cfg(feature = "pext")]
+pub const PEXT_LEN: usize = pext_end(&PEXT_ROOK);
+
+#[cfg(feature = "pext")]
+const fn pext_table() -> [u64; PEXT_LEN] {
+    let mut out = [0; PEXT_LEN];
+    let mut sq = 0;
+
+    while sq < 64 {
+        let mut j = 0;
+
+        while j < 2 {
+            let ((mask, offset), rays) = if j == 0 {
+                (PEXT_BISHOP[sq], &BISHOP_RAYS)
+            } else {
+                (PEXT_ROOK[sq], &ROOK_RAYS)
+            };
+            // subsets of the mask come out in the same order as their pext
+            let mut occ: u64 = 0;
+            let mut i = offset;
+
+            loop {
+                out[i] = slider_att(sq, rays, occ);
+                occ = occ.wrapping_sub(mask) & mask;
+                i += 1;
+
+                if occ == 0 {
+                    break;
+                }
+            }
+            j += 1;
+        }
+        sq += 1;
+    }
+
+    out
+}
+
+// the planes of `piece` standing on `sq`, as in piece_to_sq
+const fn planes(piece: u8, sq: usize) -> [u64; 4] {
+    let mut out = [0; 4];
+    let mut i = 0;
+
+    while i < 4 {
+        out[i] = ((piece as u64) >> (3 - i) & 1) << sq;
+        i += 1;
+    }
+
+    out
+}
+
+const fn xor_planes(a: [u64; 4], b: [u64; 4]) -> [u64; 4] {
+    [a[0] ^ b[0], a[1] ^ b[1], a[2] ^ b[2], a[3] ^ b[3]]
+}
+
+// diff of a `pawn` taking `opp` en passant
+const fn ep(pawn: u8, opp: u8, from: usize, to: usize, cap: usize) -> u64x4 {
+    let diff = xor_planes(planes(pawn, from), planes(pawn, to));
+
+    u64x4::from_array(xor_planes(diff, planes(opp, cap)))
+}
+
+const fn ep_table() -> [[u64x4; 2]; 2] {
+    [
+        [ep(1, 9, 39, 32, 31), ep(1, 9, 41, 32, 33)],
+        [ep(9, 1, 15, 24, 23), ep(9, 1, 17, 24, 25)],
+    ]
+}
+
+const fn min(a: usize, b: usize) -> usize {
+    if a < b {
+        a
+    } else {
+        b
+    }
+}
+
+// (squares that mustn't be attacked, squares that must be empty, diff)
+// for castling with the king and rook on the given files
+const fn castle(ksq: usize, rsq: usize) -> (u64, u64, [u64; 4]) {
+    // destination files of the king and rook
+    let (kto, rto) = if ksq > rsq { (1, 2) } else { (5, 4) };
+
+    if ksq == rsq {
+        return (0, 0, [0; 4]);
+    }
+
+    let threat = ((1 << (ksq.abs_diff(kto) + 1)) - 1) << min(ksq, kto);
+
+    let mut empty = ((1 << ksq.abs_diff(kto)) - 1) << min(ksq + 1, kto);
+    empty |= ((1 << rsq.abs_diff(rto)) - 1) << min(rsq + 1, rto);
+    empty &= !(1 << ksq);
+    empty &= !(1 << rsq);
+
+    let mut diff = xor_planes(planes(13, ksq), planes(15, rsq));
+    diff = xor_planes(diff, planes(13, kto));
+    diff = xor_planes(diff, planes(14, rto));
+
+    (threat, empty, diff)
+}
+
+const fn castle_table() -> [[[(u64, u64, u64x4); 8]; 8]; 2] {
+    let mut out = [[[(0, 0, u64x4::from_array([0; 4])); 8]; 8]; 2];
+    let mut ksq = 0;
+
+    while ksq < 8 {
+        let mut rsq = 0;
+
+        while rsq < 8 {
+            let (threat, empty, diff) = castle(ksq, rsq);
+
+            out[0][ksq][rsq] = (
+                threat,
+                empty,
+                u64x4::from_array([0, diff[1], diff[2], diff[3]]),
+            );
+            out[1][ksq][rsq] = (
+                threat << 56,
+                empty << 56,
+                u64x4::from_array([diff[0] << 56, diff[1] << 56, diff[2] << 56, diff[3] << 56]),
+            );
+            rsq += 1;
+        }
+        ksq += 1;
+    }
+
+    out
+}
+
 pub struct Tables {
-    pub white_pawn_takes: Vec<u64>,
-    pub black_pawn_takes: Vec<u64>,
-    pub bishop: Vec<(u64, u64, u64)>,
-    pub rook: Vec<(u64, u64, u64)>,
-    pub knight: Vec<u64>,
-    pub king: Vec<u64>,
-    pub magic: Vec<u64>,
-    pub castles: Vec<Vec<Vec<(u64, u64, u64x4)>>>,
-    pub en_pass: Vec<Vec<u64x4>>,
+    pub white_pawn_takes: [u64; 64],
+    pub black_pawn_takes: [u64; 64],
+    pub bishop: [(u64, u64, u64); 64],
+    pub rook: [(u64, u64, u64); 64],
+    pub knight: [u64; 64],
+    pub king: [u64; 64],
+    #[cfg(not(feature = "pext"))]
+    pub magic: [u64; MAGIC_LEN],
+    #[cfg(feature = "pext")]
+    pub pext_bishop: [(u64, usize); 64],
+    #[cfg(feature = "pext")]
+    pub pext_rook: [(u64, usize); 64],
+    #[cfg(feature = "pext")]
+    pub pext: [u64; PEXT_LEN],
+    pub castles: [[[(u64, u64, u64x4); 8]; 8]; 2],
+    pub en_pass: [[u64x4; 2]; 2],
 }
 
 impl Tables {
-    pub fn new() -> Self {
+    const fn new() -> Self {
         Self {
-            white_pawn_takes: gen_move_table(&[(-1, 1), (1, 1)]),
-            black_pawn_takes: gen_move_table(&[(-1, -1), (1, -1)]),
-            knight: gen_move_table(&[(1, 2),
-                (-1, 2),
-                (1, -2),
-                (-1, -2),
-                (2, 1),
-                (-2, 1),
-                (2, -1),
-                (-2, -1)]),
-            king: gen_move_table(&[(-1, -1),
-                (0, -1),
-                (1, -1),
-                (-1, 0),
-                (1, 0),
-                (-1, 1),
-                (0, 1),
-                (1, 1)]),
-            bishop: gen_sliding_table(true),
-            rook: gen_sliding_table(false),
-            magic: gen_magic_table(),
-            castles: gen_castle_table(),
-            en_pass: gen_ep_table(),
+            white_pawn_takes: move_table(&[(-1, 1), (1, 1)]),
+            black_pawn_takes: move_table(&[(-1, -1), (1, -1)]),
+            knight: move_table(&KNIGHT_DELTAS),
+            king: move_table(&KING_DELTAS),
+            bishop: BISHOP_TABLE,
+            rook: ROOK_TABLE,
+            #[cfg(not(feature = "pext"))]
+            magic: magic_table(),
+            #[cfg(feature = "pext")]
+            pext_bishop: PEXT_BISHOP,
+            #[cfg(feature = "pext")]
+            pext_rook: PEXT_ROOK,
+            #[cfg(feature = "pext")]
+            pext: pext_table(),
+            castles: castle_table(),
+            en_pass: ep_table(),
+        }
+    }
+}
+
+// the slider tables take a few seconds to evaluate
+#[allow(long_running_const_eval)]
+pub static TABLES: Tables = Tables::new();
+
+#[test]
+fn t_magics() {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let bishop: Vec<_> = BISHOP_MAGICS.iter().copied().zip(BISHOP_OFFSETS).collect();
+    let rook: Vec<_> = ROOK_MAGICS.iter().copied().zip(ROOK_OFFSETS).collect();
+
+    assert!(build_magic_table(&bishop, &rook, MAGIC_LEN).is_some());
+    assert!(build_magic_table(&rook, &bishop, MAGIC_LEN).is_none());
+
+    let mut rng = StdRng::seed_from_u64(0);
+
+    // rook searches in the corners take too long for a debug build
+    for (sq, bishop) in [(0, true), (9, true), (63, true), (27, false)] {
+        let magic = find_magic(sq, bishop, &mut rng);
+        let mut table = vec![0; 1 << (64 - magic_shift(bishop))];
+
+        assert!(fill_magic(&mut table, sq, bishop, magic, 0));
+    }
+}
+
+#[cfg(feature = "pext")]
+#[test]
+fn t_pext() {
+    for sq in 0..64 {
+        for (bishop, (mask, offset)) in [
+            (true, TABLES.pext_bishop[sq]),
+            (false, TABLES.pext_rook[sq]),
+        ] {
+            for (occ, att) in gen_occ_att(sq, bishop) {
+                assert_eq!(TABLES.pext[offset + pext(occ | !mask, mask) as usize], att);
+            }
         }
     }
 }
 
-lazy_static! {
-    pub static ref TABLES: Tables = Tables::new();
+#[test]
+fn t_const_tables() {
+    let tables = &TABLES;
+
+    assert_eq!(
+        tables.white_pawn_takes.to_vec(),
+        gen_move_table(&[(-1, 1), (1, 1)])
+    );
+    assert_eq!(
+        tables.black_pawn_takes.to_vec(),
+        gen_move_table(&[(-1, -1), (1, -1)])
+    );
+    assert_eq!(tables.knight.to_vec(), gen_move_table(&KNIGHT_DELTAS));
+    assert_eq!(tables.king.to_vec(), gen_move_table(&KING_DELTAS));
+    assert_eq!(tables.bishop.to_vec(), gen_sliding_table(true));
+    assert_eq!(tables.rook.to_vec(), gen_sliding_table(false));
+
+    #[cfg(not(feature = "pext"))]
+    assert_eq!(tables.magic.to_vec(), gen_magic_table());
+
+    #[cfg(feature = "pext")]
+    {
+        let (pext_bishop, pext_rook, pext) = gen_pext_table();
+
+        assert_eq!(tables.pext_bishop.to_vec(), pext_bishop);
+        assert_eq!(tables.pext_rook.to_vec(), pext_rook);
+        assert_eq!(tables.pext.to_vec(), pext);
+    }
+
+    let castles = gen_castle_table();
+    let en_pass = gen_ep_table();
+
+    for black in 0..2 {
+        let rows: Vec<_> = tables.castles[black].iter().map(|r| r.to_vec()).collect();
+
+        assert_eq!(rows, castles[black]);
+        assert_eq!(tables.en_pass[black].to_vec(), en_pass[black]);
+    }
 }
@@ -8,15 +8,23 @@ extern crate test;
 #[macro_use]
 extern crate lazy_static;
 
+mod active;
 mod board;
+mod game;
 mod gen_moves;
 mod gen_tables;
 mod moves;
 mod search;
+mod tablebase;
 mod tt;
+mod types;
+mod variant;
 mod eval {
     pub use crate::gen_moves::eval::*;
 }
+mod nnue {
+    pub use crate::gen_moves::nnue::*;
+}
 mod uci {
     pub use crate::search::uci::*;
 }
@@ -34,14 +42,44 @@ use std::path::Path;
 #[cfg(feature = "tuning")]
 fn main() {
     // tuning::positions_from_games("tuning_games2.pgn", "tuning_positions2.txt");
+    // in the format read by --evalfile
     println!(
-        "{:#?}",
+        "{}",
         tuning::tune("tuning_positions2.txt", "tuning_positions2.cache", &PARAMS)
     );
 }
 
 #[cfg(not(feature = "tuning"))]
 fn main() {
+    if env::args().any(|arg| arg == "--magics") {
+        gen_tables::print_magics();
+        return;
+    }
+
+    if let Some(path) = env::args().skip_while(|arg| arg != "--evalfile").nth(1) {
+        match eval::EvalParams::load(&path) {
+            Ok(params) => eval::EvalParams::set_active(params),
+            Err(()) => {
+                eprintln!("could not load eval file {}", path);
+                return;
+            }
+        }
+    }
+
+    if let Some(dir) = env::args().skip_while(|arg| arg != "--gentb").nth(1) {
+        let mut tbs = tablebase::Tablebases::default();
+
+        for material in tablebase::Material::all(tablebase::MAX_MEN) {
+            println!("{}", material.name());
+            tbs.generate(material);
+        }
+
+        if tbs.save(&dir).is_err() {
+            eprintln!("could not write tables to {}", dir);
+        }
+        return;
+    }
+
     ucimanager(BufReader::new(io::stdin()));
     // let mut board = Board::from_fen("r1bqkbnr/pppp1ppp/8/8/3nP3/5N2/PPP2PPP/RNB1KB1R w KQkq - ");
     // let mut board = Board::from_fen(START_FEN);
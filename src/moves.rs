@@ -1,8 +1,11 @@
 use crate::board::*;
 use crate::gen_tables::*;
+use crate::types::*;
+use crate::variant::*;
 
 const SQUARE: u16 = 0x3f;
 const PIECE: u16 = 0x7;
+const DROP: u16 = 0x8;
 
 #[derive(Clone, Copy, PartialEq, Default)]
 pub struct Move(pub u16);
@@ -30,6 +33,45 @@ impl Move {
         (self.0 & PIECE) as usize
     }
 
+    pub fn from_squares(start: Square, end: Square, promotion: Option<PieceKind>) -> Self {
+        Move::pack(
+            start.index(),
+            end.index(),
+            promotion.map_or(0, |p| p.code() as usize),
+        )
+    }
+
+    /// A crazyhouse drop of `kind` onto `sq`.
+    pub fn drop(kind: PieceKind, sq: Square) -> Self {
+        Move(Move::pack(sq.index(), sq.index(), kind.code() as usize).0 | DROP)
+    }
+
+    pub fn is_drop(&self) -> bool {
+        self.0 & DROP != 0
+    }
+
+    pub fn dropped(&self) -> Option<PieceKind> {
+        if self.is_drop() {
+            PieceKind::from_code(self.piece() as u8)
+        } else {
+            None
+        }
+    }
+
+    pub fn start_square(&self) -> Square {
+        Square::new(self.start()).unwrap()
+    }
+    pub fn end_square(&self) -> Square {
+        Square::new(self.end()).unwrap()
+    }
+    pub fn promotion(&self) -> Option<PieceKind> {
+        if self.is_drop() {
+            None
+        } else {
+            PieceKind::from_code(self.piece() as u8)
+        }
+    }
+
     pub fn from_uci(s: &str) -> Self {
         s.parse().unwrap()
     }
@@ -39,6 +81,17 @@ use std::fmt;
 
 impl fmt::Display for Move {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_drop() {
+            let piece = self.piece();
+
+            return write!(
+                f,
+                "{}@{}",
+                &FEN_PIECES[piece..piece + 1],
+                str_from_sq(self.end())
+            );
+        }
+
         write!(f, "{}", str_from_sq(self.start()))?;
         write!(f, "{}", str_from_sq(self.end()))?;
 
@@ -64,21 +117,72 @@ impl FromStr for Move {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((piece, sq)) = s.split_once('@') {
+            let kind = piece.parse::<Piece>()?.kind;
+
+            return Ok(Move::drop(kind, sq.parse()?));
+        }
+
         let s = s.to_ascii_lowercase();
-        let start = sq_from_str(&s[0..2]);
-        let end = sq_from_str(&s[2..4]);
-        let piece = if s.len() >= 5 {
-            FEN_PIECES.find(&s[4..5]).unwrap_or(8) - 8
-        } else {
-            0
+        let start = s.get(0..2).ok_or(())?.parse::<Square>()?;
+        let end = s.get(2..4).ok_or(())?.parse::<Square>()?;
+        let piece = match s.get(4..) {
+            Some("") => 0,
+            Some(p) => p.parse::<Piece>()?.kind.code() as usize,
+            None => return Err(()),
         };
 
-        Ok(Move::pack(start, end, piece))
+        Ok(Move::pack(start.index(), end.index(), piece))
     }
 }
 
+/// What `make_move` changed, for `unmake_move` to put back. The bitplanes
+/// are kept as a diff and the rest of the state as it was before the move.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Undo {
+    diff: u64x4,
+    hash: u64,
+    checks: [u8; 2],
+    pockets: [[u8; 8]; 2],
+    promoted: u64,
+}
+
 impl Board {
     pub fn do_move(&self, mov: Move) -> Board {
+        let mut out = self.do_move_pieces(mov);
+
+        out.update_variant(self);
+        out
+    }
+
+    /// Plays `mov` in place, for walking long move sequences without keeping
+    /// every board along the way.
+    pub fn make_move(&mut self, mov: Move) -> Undo {
+        let next = self.do_move(mov);
+        let undo = Undo {
+            diff: self.b ^ next.b,
+            hash: self.hash,
+            checks: self.checks,
+            pockets: self.pockets,
+            promoted: self.promoted,
+        };
+
+        *self = next;
+        undo
+    }
+
+    /// Takes back the move `undo` was returned for, which must be the last
+    /// one made.
+    pub fn unmake_move(&mut self, undo: Undo) {
+        self.b ^= undo.diff;
+        self.black ^= true;
+        self.hash = undo.hash;
+        self.checks = undo.checks;
+        self.pockets = undo.pockets;
+        self.promoted = undo.promoted;
+    }
+
+    fn do_move_pieces(&self, mov: Move) -> Board {
         let mut out = self.clone();
         let (start, end, piece) = mov.unpack();
         let cur_occ = if self.black {
@@ -88,8 +192,18 @@ impl Board {
         };
         out.black ^= true;
 
+        // Drops
+        if mov.is_drop() {
+            // the en passant square may be dropped on, so it goes first
+            out.remove_takeable_empty();
+            out.b |= piece_to_sq(piece as u8 | (self.black as u8) << 3) << end as u64;
+
+            out.update_hash(self);
+
+            out
+        }
         // En Passant
-        if self.pawns() & 1 << start != 0 && self.takeable_empties() & 1 << end != 0 {
+        else if self.pawns() & 1 << start != 0 && self.takeable_empties() & 1 << end != 0 {
             out.remove_takeable_empty();
 
             out.b ^= TABLES.en_pass[self.black as usize][(end % 8 > start % 8) as usize]
@@ -102,7 +216,9 @@ impl Board {
         // Castling (Chess960)
         else if self.kings() & 1 << start != 0
             && self.rooks() & 1 << end != 0
-            && (self.white() & (1 << start | 1 << end)).count_ones() % 2 == 0
+            && (self.white() & (1 << start | 1 << end))
+                .count_ones()
+                .is_multiple_of(2)
         {
             out.b ^= TABLES.castles[self.black as usize][start % 8][end % 8].2;
 
@@ -198,6 +314,50 @@ impl Board {
             (board_diff, (s.b ^ o.b).reduce_or())
         };
 
+        let other_cur_occ = if self.black {
+            other.black()
+        } else {
+            other.white()
+        };
+
+        if other_cur_occ.count_ones() > cur_occ.count_ones() {
+            let end = (other_cur_occ & !cur_occ).trailing_zeros() as usize;
+            let kind = PieceKind::from_code(other.get_square(end as u8) & 7).unwrap();
+
+            return Move::drop(kind, Square::new(end).unwrap());
+        }
+
+        // in atomic the capturing piece explodes, so the capture is found by
+        // replaying the candidates
+        if self.variant == Variant::Atomic {
+            let (opp_occ, other_opp_occ) = if self.black {
+                (self.white(), other.white())
+            } else {
+                (self.black(), other.black())
+            };
+
+            let captured = opp_occ & !other_opp_occ | self.takeable_empties();
+
+            if opp_occ & !other_opp_occ != 0 {
+                for end in LocStack(captured) {
+                    let attackers = self.get_att_def(self.occ(), end) & cur_occ;
+
+                    for start in LocStack(attackers & !other_cur_occ) {
+                        let piece = if self.pawns() & 1 << start != 0 && !(8..56).contains(&end) {
+                            4 | (self.black as usize) << 3
+                        } else {
+                            0
+                        };
+                        let mov = Move::pack(start, end, piece);
+
+                        if self.do_move(mov) == *other {
+                            return mov;
+                        }
+                    }
+                }
+            }
+        }
+
         let mut castle = 64;
         {
             let king_start = (self.kings() & cur_occ).trailing_zeros() as usize;
@@ -326,3 +486,74 @@ fn t_moves() {
         assert_eq!(board.get_move(&board2, c960), mov);
     }
 }
+
+#[test]
+fn t_random_games() {
+    use crate::gen_moves::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    let mut rng = StdRng::seed_from_u64(0);
+
+    for variant in Variant::ALL {
+        for _ in 0..10 {
+            let mut board = Board::from_fen(START_FEN).with_variant(variant);
+
+            for _ in 0..100 {
+                let mut generator = MoveGenerator::new(board.clone());
+                generator.gen_moves();
+
+                if generator.moves.is_empty() || board.is_variant_loss() {
+                    break;
+                }
+
+                // every generated position is reached by playing its move
+                for b in &generator.moves {
+                    assert_eq!(*b, board.do_move(board.get_move(b, false)));
+                }
+
+                board = generator.moves[rng.gen_range(0..generator.moves.len())].clone();
+            }
+        }
+    }
+}
+
+#[test]
+fn t_make_move() {
+    use crate::gen_moves::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    let mut rng = StdRng::seed_from_u64(0);
+
+    for variant in Variant::ALL {
+        for _ in 0..10 {
+            let mut board = Board::from_fen(START_FEN).with_variant(variant);
+            let mut boards = Vec::new();
+            let mut undos = Vec::new();
+
+            for _ in 0..100 {
+                let mut generator = MoveGenerator::new(board.clone());
+                generator.gen_moves();
+
+                if generator.moves.is_empty() || board.is_variant_loss() {
+                    break;
+                }
+
+                let next = &generator.moves[rng.gen_range(0..generator.moves.len())];
+                let mov = board.get_move(next, false);
+
+                boards.push(board.clone());
+                undos.push(board.make_move(mov));
+
+                assert_eq!(board, boards.last().unwrap().do_move(mov));
+                assert_eq!(board, *next);
+            }
+
+            // unwinding gives back every board along the way
+            while let Some(undo) = undos.pop() {
+                board.unmake_move(undo);
+
+                assert_eq!(board, boards.pop().unwrap());
+            }
+        }
+    }
+}
@@ -0,0 +1,371 @@
+// this is a submodule of gen_moves so that it can have access to
+// MoveGenerator internals
+use super::*;
+use crate::active::*;
+use crate::eval::*;
+
+use std::fs;
+use std::simd::{cmp::SimdOrd, i16x16, i32x16, i8x16, num::SimdInt};
+use std::sync::Arc;
+
+/* network file format, all numbers little endian
+ *  "NNUE" u32 version, u32 king buckets, u32 hidden size
+ *  i16 feature weights, HIDDEN per feature
+ *  i16 feature biases, HIDDEN
+ *  i8 output weights, HIDDEN for the side to move then HIDDEN for the other
+ *  i32 output bias
+ */
+
+// the king's own half of the board, and whether it is still on its first
+// two ranks
+pub const KING_BUCKETS: usize = 4;
+// own and enemy pawns, knights, bishops, queens, kings and rooks on each
+// square, for each king bucket
+pub const FEATURES: usize = KING_BUCKETS * 2 * 6 * 64;
+pub const HIDDEN: usize = 128;
+
+const VERSION: u32 = 1;
+const FILE_LEN: usize = 16 + (FEATURES + 1) * HIDDEN * 2 + 2 * HIDDEN + 4;
+
+// quantization of the clipped accumulator and of the output weights
+const QA: i16 = 127;
+const QB: i32 = 64;
+// network output to centipawns
+const SCALE: i32 = 400;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Network {
+    feature_weights: Vec<[i16; HIDDEN]>,
+    feature_bias: [i16; HIDDEN],
+    output_weights: [i8; 2 * HIDDEN],
+    output_bias: i32,
+}
+
+/// The hidden layer from both sides' points of view, indexed by color.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Accumulator {
+    values: [[i16; HIDDEN]; 2],
+    buckets: [usize; 2],
+}
+
+// black sees the board upside down
+fn flip(persp: usize) -> usize {
+    persp * 56
+}
+
+fn king_bucket(board: &Board, persp: usize) -> usize {
+    let own = if persp == 1 {
+        board.black()
+    } else {
+        board.white()
+    };
+    // a missing king, as in antichess, counts as being on h1
+    let king = (board.kings() & own).trailing_zeros() as usize % 64;
+    let king = king ^ flip(persp);
+
+    (king >= 16) as usize * 2 + (king % 8 >= 4) as usize
+}
+
+fn feature(persp: usize, bucket: usize, piece: u8, sq: usize) -> usize {
+    // uncastled rooks are just rooks
+    let kind = (piece & 7).min(6) as usize - 1;
+    let enemy = (piece >> 3) as usize ^ persp;
+
+    ((bucket * 2 + enemy) * 6 + kind) * 64 + (sq ^ flip(persp))
+}
+
+fn add_row(values: &mut [i16; HIDDEN], row: &[i16; HIDDEN]) {
+    for (v, w) in values.chunks_exact_mut(16).zip(row.chunks_exact(16)) {
+        (i16x16::from_slice(v) + i16x16::from_slice(w)).copy_to_slice(v);
+    }
+}
+
+fn sub_row(values: &mut [i16; HIDDEN], row: &[i16; HIDDEN]) {
+    for (v, w) in values.chunks_exact_mut(16).zip(row.chunks_exact(16)) {
+        (i16x16::from_slice(v) - i16x16::from_slice(w)).copy_to_slice(v);
+    }
+}
+
+// set once a network has been loaded
+static LOADED_NETWORK: Active<Network> = Active::new();
+
+impl Network {
+    /// The network last passed to `set_active`, if any.
+    pub fn active() -> Option<Arc<Network>> {
+        LOADED_NETWORK.get()
+    }
+
+    pub fn set_active(net: Network) {
+        LOADED_NETWORK.set(net);
+    }
+
+    pub fn load(path: &str) -> Result<Self, ()> {
+        Network::from_bytes(&fs::read(path).map_err(|_| ())?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ()> {
+        if bytes.len() != FILE_LEN || &bytes[..4] != b"NNUE" {
+            return Err(());
+        }
+
+        let u32s = |b: &[u8]| {
+            b.chunks_exact(4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect::<Vec<_>>()
+        };
+        let i16s = |b: &[u8]| {
+            b.chunks_exact(2)
+                .map(|b| i16::from_le_bytes([b[0], b[1]]))
+                .collect::<Vec<_>>()
+        };
+
+        if u32s(&bytes[4..16]) != [VERSION, KING_BUCKETS as u32, HIDDEN as u32] {
+            return Err(());
+        }
+
+        let (weights, rest) = bytes[16..].split_at(FEATURES * HIDDEN * 2);
+        let (bias, rest) = rest.split_at(HIDDEN * 2);
+        let (output, output_bias) = rest.split_at(2 * HIDDEN);
+
+        let mut out = Network {
+            feature_weights: vec![[0; HIDDEN]; FEATURES],
+            feature_bias: [0; HIDDEN],
+            output_weights: [0; 2 * HIDDEN],
+            output_bias: u32s(output_bias)[0] as i32,
+        };
+
+        for (row, b) in out
+            .feature_weights
+            .iter_mut()
+            .zip(weights.chunks_exact(HIDDEN * 2))
+        {
+            row.copy_from_slice(&i16s(b));
+        }
+
+        out.feature_bias.copy_from_slice(&i16s(bias));
+
+        for (w, b) in out.output_weights.iter_mut().zip(output) {
+            *w = *b as i8;
+        }
+
+        Ok(out)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = b"NNUE".to_vec();
+
+        for n in [VERSION, KING_BUCKETS as u32, HIDDEN as u32] {
+            out.extend(n.to_le_bytes());
+        }
+
+        for row in self.feature_weights.iter().chain([&self.feature_bias]) {
+            out.extend(row.iter().flat_map(|w| w.to_le_bytes()));
+        }
+
+        out.extend(self.output_weights.iter().map(|w| *w as u8));
+        out.extend(self.output_bias.to_le_bytes());
+
+        out
+    }
+
+    /// Evaluates from the point of view of the side to move.
+    pub fn eval(&self, acc: &Accumulator, black: bool) -> i32 {
+        let sides = [&acc.values[black as usize], &acc.values[!black as usize]];
+        let mut sum = i32x16::splat(0);
+
+        for (values, weights) in sides.iter().zip(self.output_weights.chunks_exact(HIDDEN)) {
+            for (v, w) in values.chunks_exact(16).zip(weights.chunks_exact(16)) {
+                let v = i16x16::from_slice(v).simd_clamp(i16x16::splat(0), i16x16::splat(QA));
+
+                sum += v.cast::<i32>() * i8x16::from_slice(w).cast::<i32>();
+            }
+        }
+
+        (sum.reduce_sum() + self.output_bias) * SCALE / (QA as i32 * QB)
+    }
+}
+
+impl Accumulator {
+    pub fn new(net: &Network, board: &Board) -> Self {
+        let mut out = Accumulator {
+            values: [net.feature_bias; 2],
+            buckets: [0; 2],
+        };
+
+        out.refresh(net, board, 0);
+        out.refresh(net, board, 1);
+        out
+    }
+
+    fn refresh(&mut self, net: &Network, board: &Board, persp: usize) {
+        let bucket = king_bucket(board, persp);
+
+        self.values[persp] = net.feature_bias;
+        self.buckets[persp] = bucket;
+
+        for sq in LocStack(board.occ()) {
+            let piece = board.get_square(sq as u8);
+            let row = &net.feature_weights[feature(persp, bucket, piece, sq)];
+
+            add_row(&mut self.values[persp], row);
+        }
+    }
+
+    /// The accumulator of `board`, reached by a move from `prev`. Only the
+    /// squares that differ between the two are looked at, unless a king
+    /// changed buckets.
+    pub fn update(&self, net: &Network, prev: &Board, board: &Board) -> Self {
+        let mut out = self.clone();
+        let changed = (board.b ^ prev.b).reduce_or();
+
+        for persp in 0..2 {
+            let bucket = king_bucket(board, persp);
+
+            if bucket != self.buckets[persp] {
+                out.refresh(net, board, persp);
+                continue;
+            }
+
+            for sq in LocStack(changed) {
+                let (old, new) = (prev.get_square(sq as u8), board.get_square(sq as u8));
+
+                if old & 7 != 0 {
+                    sub_row(
+                        &mut out.values[persp],
+                        &net.feature_weights[feature(persp, bucket, old, sq)],
+                    );
+                }
+
+                if new & 7 != 0 {
+                    add_row(
+                        &mut out.values[persp],
+                        &net.feature_weights[feature(persp, bucket, new, sq)],
+                    );
+                }
+            }
+        }
+
+        out
+    }
+}
+
+impl MoveGenerator {
+    /// Like `eval`, but with the network scoring the positions that are not
+    /// decided yet. Stalemates score `draw`.
+    pub fn eval_nnue(&mut self, board: Board, net: &Network, acc: &Accumulator, draw: i32) -> i32 {
+        if board.is_variant_loss() {
+            return -CHECKMATE;
+        }
+
+        self.set_board(board);
+
+        if !self.has_moves() {
+            if self.checks == 0 {
+                return draw;
+            } else {
+                return -CHECKMATE;
+            }
+        }
+
+        net.eval(acc, self.board.black)
+    }
+}
+
+#[cfg(test)]
+fn random_network(seed: u64) -> Network {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut out = Network {
+        feature_weights: vec![[0; HIDDEN]; FEATURES],
+        feature_bias: [0; HIDDEN],
+        output_weights: [0; 2 * HIDDEN],
+        output_bias: rng.gen_range(-1000..1000),
+    };
+
+    for w in out.feature_weights.iter_mut().flatten() {
+        *w = rng.gen_range(-32..32);
+    }
+
+    for w in out.feature_bias.iter_mut() {
+        *w = rng.gen_range(-32..96);
+    }
+
+    for w in out.output_weights.iter_mut() {
+        *w = rng.gen_range(-127..=127);
+    }
+
+    out
+}
+
+#[test]
+fn t_nnue_file() {
+    let net = random_network(0);
+    let bytes = net.to_bytes();
+
+    assert_eq!(bytes.len(), FILE_LEN);
+    assert_eq!(Network::from_bytes(&bytes), Ok(net));
+    assert_eq!(Network::from_bytes(&bytes[1..]), Err(()));
+
+    let mut bytes = bytes;
+    bytes[8] = 8;
+    assert_eq!(Network::from_bytes(&bytes), Err(()));
+}
+
+#[test]
+fn t_nnue_update() {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    let net = random_network(1);
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut generator = MoveGenerator::empty();
+
+    for _ in 0..10 {
+        let mut board = Board::from_fen(START_FEN);
+        let mut acc = Accumulator::new(&net, &board);
+
+        for _ in 0..100 {
+            generator.set_board(board.clone());
+            generator.gen_moves();
+
+            if generator.moves.is_empty() {
+                break;
+            }
+
+            let next = generator.moves[rng.gen_range(0..generator.moves.len())].clone();
+
+            acc = acc.update(&net, &board, &next);
+            board = next;
+
+            assert_eq!(acc, Accumulator::new(&net, &board));
+        }
+    }
+}
+
+#[test]
+fn t_nnue_symmetry() {
+    let net = random_network(2);
+
+    // the same position with the colors swapped
+    let board1 = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq -");
+    let board2 = Board::from_fen("rnbqkb1r/pppp1ppp/5n2/4p3/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq -");
+
+    let eval = |board: &Board| {
+        let mut generator = MoveGenerator::empty();
+        let acc = Accumulator::new(&net, board);
+
+        generator.eval_nnue(board.clone(), &net, &acc, 0)
+    };
+
+    assert_eq!(eval(&board1), eval(&board2));
+
+    // mates are found without asking the network
+    let mated = Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq -");
+    assert_eq!(eval(&mated), -CHECKMATE);
+
+    // and stalemates score the draw they are given
+    let stalemate = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - -");
+    let acc = Accumulator::new(&net, &stalemate);
+    let mut generator = MoveGenerator::empty();
+    assert_eq!(generator.eval_nnue(stalemate, &net, &acc, -20), -20);
+}
@@ -1,12 +1,16 @@
 use crate::board::*;
 use crate::eval::*;
+use crate::game::*;
 use crate::gen_moves::*;
 use crate::moves::*;
+use crate::nnue::*;
+use crate::tablebase::*;
 use crate::tt::*;
+use crate::variant::*;
 
 use rand::{thread_rng, Rng};
-use std::collections::HashMap;
 use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 #[path = "uci.rs"]
@@ -14,6 +18,11 @@ pub mod uci;
 
 use crate::search::uci::*;
 
+// entries in each thread's pawn hash table
+const PAWN_TABLE_LEN: usize = 1 << 14;
+// entries in each thread's eval cache
+const EVAL_CACHE_LEN: usize = 1 << 16;
+
 pub struct Searcher {
     gens: Vec<MoveGenerator>,
     c960: bool,
@@ -22,10 +31,23 @@ pub struct Searcher {
     curr_depth: u8,
     time: u8,
     stop_time: Instant,
-    prev_pos: HashMap<u64, u8>,
     history: [[[usize; 64]; 64]; 2],
     tt: TT,
-    pawn_tt: TT,
+    pawn_tt: PawnTable,
+    // full evals of positions seen during the current search
+    eval_cache: TT,
+    params: Arc<EvalParams>,
+    use_nnue: bool,
+    nnue: Option<Arc<Network>>,
+    // the boards on the current line with their accumulators, while a
+    // network is in use
+    accs: Vec<(Board, Accumulator)>,
+    tbs: Option<Arc<Tablebases>>,
+    // how much the side to move at the root dislikes draws, in centipawns
+    contempt: i32,
+    root_black: bool,
+    // the game up to the root, then the line being searched
+    game: Game,
     recv: Receiver<SearcherCommand>,
     stop: Receiver<bool>,
     id: usize,
@@ -91,13 +113,7 @@ pub fn show_ibv(n: i32) -> String {
 }
 
 impl Searcher {
-    pub fn new(
-        tt: TT,
-        pawn_tt: TT,
-        recv: Receiver<SearcherCommand>,
-        stop: Receiver<bool>,
-        id: usize,
-    ) -> Self {
+    pub fn new(tt: TT, recv: Receiver<SearcherCommand>, stop: Receiver<bool>, id: usize) -> Self {
         Self {
             gens: Vec::new(),
             c960: false,
@@ -106,10 +122,18 @@ impl Searcher {
             time: 0,
             stop_time: Instant::now() + Duration::from_secs(3155760000),
             curr_depth: 0,
-            prev_pos: HashMap::new(),
             history: [[[0usize; 64]; 64]; 2],
             tt,
-            pawn_tt,
+            pawn_tt: PawnTable::with_len(PAWN_TABLE_LEN),
+            eval_cache: TT::with_len(EVAL_CACHE_LEN),
+            params: EvalParams::active(),
+            use_nnue: false,
+            nnue: None,
+            accs: Vec::new(),
+            tbs: None,
+            contempt: 0,
+            root_black: false,
+            game: Game::from_fen(START_FEN),
             recv,
             stop,
             id,
@@ -125,10 +149,18 @@ impl Searcher {
             time: 0,
             stop_time: Instant::now() + Duration::from_secs(3155760000),
             curr_depth: 0,
-            prev_pos: HashMap::new(),
             history: [[[0usize; 64]; 64]; 2],
             tt: TT::with_len(ttsize),
-            pawn_tt: TT::with_len(1024),
+            pawn_tt: PawnTable::with_len(1024),
+            eval_cache: TT::with_len(EVAL_CACHE_LEN),
+            params: EvalParams::active(),
+            use_nnue: false,
+            nnue: None,
+            accs: Vec::new(),
+            tbs: None,
+            contempt: 0,
+            root_black: false,
+            game: Game::from_fen(START_FEN),
             recv: channel().1,
             stop: channel().1,
             id: 0,
@@ -179,12 +211,83 @@ impl Searcher {
         }
     }
 
-    pub fn quiesce(&mut self, board: Board, mut alpha: i32, beta: i32) -> i32 {
+    // keeps the accumulators in step with the line being searched
+    fn push_accumulator(&mut self, board: &Board) {
+        if let Some(net) = &self.nnue {
+            let acc = match self.accs.last() {
+                Some((prev, acc)) => acc.update(net, prev, board),
+                None => Accumulator::new(net, board),
+            };
+
+            self.accs.push((board.clone(), acc));
+        }
+    }
+
+    // the score of a draw for the side to move, in eval units
+    fn draw_score(&self, board: &Board) -> i32 {
+        invert_if(board.black != self.root_black, -self.contempt)
+    }
+
+    // `alpha` and `beta` are the window in eval units
+    fn evaluate(
+        &mut self,
+        generator: &mut MoveGenerator,
+        board: Board,
+        alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        match (&self.nnue, self.accs.last()) {
+            (Some(net), Some((_, acc))) if board.variant == Variant::Standard => {
+                let draw = self.draw_score(&board);
+                generator.eval_nnue(board, net, acc, draw)
+            }
+            _ => {
+                if let Some(score) = self.eval_cache.read(board.hash) {
+                    return score as i32;
+                }
+
+                let hash = board.hash;
+                let draw = self.draw_score(&board);
+                let params = EvalParams::for_variant(board.variant, &self.params);
+                let score = generator.eval_with_params(
+                    board,
+                    &mut self.pawn_tt,
+                    params,
+                    Some((alpha, beta)),
+                    draw,
+                );
+
+                // rough scores from lazy evals are always outside the window
+                if alpha < score && score < beta {
+                    self.eval_cache.write(hash, score as u32 as u64);
+                }
+
+                score
+            }
+        }
+    }
+
+    pub fn quiesce(&mut self, board: Board, alpha: i32, beta: i32) -> i32 {
+        self.push_accumulator(&board);
+        let out = self.quiesce_node(board, alpha, beta);
+        self.accs.pop();
+
+        out
+    }
+
+    fn quiesce_node(&mut self, board: Board, mut alpha: i32, beta: i32) -> i32 {
+        if board.is_variant_loss() {
+            return -CHECKMATE * 4;
+        } else if board.variant == Variant::Antichess {
+            return self.quiesce_antichess(board, alpha, beta);
+        }
+
         let cut = ibv_exact(beta);
 
         let mut generator = self.gens.pop().unwrap_or(MoveGenerator::empty());
 
-        let mut score = generator.eval(board.clone(), &mut self.pawn_tt) * 4;
+        let window = (from_ibv(alpha), from_ibv(beta));
+        let mut score = self.evaluate(&mut generator, board.clone(), window.0, window.1) * 4;
 
         if score >= cut {
             self.gens.push(generator);
@@ -196,8 +299,11 @@ impl Searcher {
 
         generator.set_board(board.clone());
         generator.gen_tactical();
-        generator.moves.retain(|b| board.eval_see(b) >= 0);
-        generator.moves.sort_by_cached_key(|b| -board.eval_see(b));
+        let params = &*self.params;
+        generator.moves.retain(|b| board.eval_see(b, params) >= 0);
+        generator
+            .moves
+            .sort_by_cached_key(|b| -board.eval_see(b, params));
         let mut iter = generator.moves.drain(..);
 
         while let Some(board2) = iter.next() {
@@ -218,6 +324,46 @@ impl Searcher {
         alpha
     }
 
+    // captures are compulsory in antichess, so there is no standing pat
+    // while one is available
+    fn quiesce_antichess(&mut self, board: Board, mut alpha: i32, beta: i32) -> i32 {
+        let cut = ibv_exact(beta);
+
+        let mut generator = self.gens.pop().unwrap_or(MoveGenerator::empty());
+
+        generator.set_board(board.clone());
+        generator.gen_tactical();
+
+        if generator.moves.is_empty() {
+            let score = self.evaluate(&mut generator, board, from_ibv(alpha), from_ibv(beta)) * 4;
+
+            self.gens.push(generator);
+            return score;
+        }
+
+        generator
+            .moves
+            .sort_by_cached_key(|b| -board.eval_mvv_lva(b, &self.params));
+        let mut iter = generator.moves.drain(..);
+
+        while let Some(board2) = iter.next() {
+            let score = -self.quiesce(board2, -beta, -alpha);
+
+            if score >= cut {
+                std::mem::drop(iter);
+                self.gens.push(generator);
+                return ibv_min(score);
+            }
+            if score > alpha {
+                alpha = score;
+            }
+        }
+
+        std::mem::drop(iter);
+        self.gens.push(generator);
+        alpha
+    }
+
     fn write_tt(&mut self, hash: u64, score: i32, depth: u8, mov: Move) {
         let (hash2, res) = self.tt.force_read(hash);
         let (score2, time2, depth2, mov2) = unpack_search(res);
@@ -236,35 +382,48 @@ impl Searcher {
         }
     }
 
-    fn incr_prev_pos(&mut self, hash: u64) {
-        if let Some(i) = self.prev_pos.get_mut(&hash) {
-            *i += 1;
-        } else {
-            self.prev_pos.insert(hash, 1);
-        }
-    }
-
-    fn decr_prev_pos(&mut self, hash: u64) {
-        if let Some(i) = self.prev_pos.get_mut(&hash) {
-            *i -= 1;
+    pub fn alphabeta(
+        &mut self,
+        board: Board,
+        alpha: i32,
+        beta: i32,
+        depth: u8,
+    ) -> Result<i32, bool> {
+        self.push_accumulator(&board);
+        let out = self.alphabeta_node(board, alpha, beta, depth);
+        self.accs.pop();
 
-            if *i == 0 {
-                self.prev_pos.remove(&hash);
-            }
-        }
+        out
     }
 
-    pub fn alphabeta(
+    fn alphabeta_node(
         &mut self,
         board: Board,
         mut alpha: i32,
         beta: i32,
         depth: u8,
     ) -> Result<i32, bool> {
-        // Threefold repetition
-        if let Some(1..) = self.prev_pos.get(&board.hash) {
-            if depth != self.curr_depth {
-                return Ok(0);
+        // Threefold repetition and the fifty-move rule
+        if depth != self.curr_depth
+            && (self.game.repetitions() > 1 || self.game.halfmove_clock() >= 100)
+        {
+            return Ok(self.draw_score(&board) * 4);
+        }
+
+        // Variant wins (third check, king on the hill)
+        if board.is_variant_loss() {
+            return Ok(-CHECKMATE * 4);
+        }
+
+        // Endgame tablebases, except at the root, which needs a move
+        if depth != self.curr_depth {
+            if let Some(wdl) = self.tbs.as_ref().and_then(|tbs| tbs.probe(&board)) {
+                let score = match wdl {
+                    Wdl::Draw => self.draw_score(&board),
+                    _ => wdl.score(),
+                };
+
+                return Ok(score * 4);
             }
         }
 
@@ -306,13 +465,21 @@ impl Searcher {
         }
 
         // Null move Pruning
-        if depth > 3 && !board.is_late_endgame() && !board.in_check() {
+        if depth > 3
+            && !board.is_late_endgame()
+            && !board.in_check()
+            && board.variant != Variant::Antichess
+        {
             let mut board2 = board.clone();
             board2.black ^= true;
             board2.remove_takeable_empty();
             board2.update_hash(&board);
 
-            let score = -self.alphabeta(board2, -cut - 4, -cut, depth - 3)?;
+            self.game.push_board(Move(0), board2.clone());
+            let score = self.alphabeta(board2, -cut - 4, -cut, depth - 3);
+            self.game.pop();
+
+            let score = -score?;
 
             if score > cut {
                 return Ok(score);
@@ -325,8 +492,11 @@ impl Searcher {
         generator.gen_moves();
 
         if generator.moves.is_empty() {
-            if generator.get_checks() == 0 {
-                return Ok(0);
+            if board.variant == Variant::Antichess {
+                // running out of moves wins
+                return Ok(CHECKMATE * 4);
+            } else if generator.get_checks() == 0 {
+                return Ok(self.draw_score(&board) * 4);
             } else {
                 return Ok(-CHECKMATE * 4);
             }
@@ -350,7 +520,7 @@ impl Searcher {
                 if Some(b.clone()) == best_move {
                     -1000000
                 } else if board.is_capture(b) {
-                    -board.eval_see(b) as i64
+                    -board.eval_see(b, &self.params) as i64
                 } else {
                     let mov = board.get_move(b, self.c960);
                     let history = self.history[board.black as usize][mov.start()][mov.end()];
@@ -381,9 +551,9 @@ impl Searcher {
 
             // Principal Variation Search
             if pvs {
-                self.incr_prev_pos(board.hash);
+                self.game.push_board(mov, board2.clone());
                 let s = self.alphabeta(board2.clone(), -alpha - 4, -alpha, depth - reduction);
-                self.decr_prev_pos(board.hash);
+                self.game.pop();
 
                 s?;
                 let s = -s.unwrap();
@@ -394,9 +564,9 @@ impl Searcher {
             }
 
             // Alpha-Beta
-            self.incr_prev_pos(board.hash);
+            self.game.push_board(mov, board2.clone());
             let score = self.alphabeta(board2.clone(), -beta, -alpha, depth - reduction);
-            self.decr_prev_pos(board.hash);
+            self.game.pop();
 
             score?;
             let score = -score.unwrap();
@@ -488,37 +658,71 @@ impl Searcher {
         while self.stop.try_recv().is_ok() {}
         self.gens.clear();
 
-        for depth in min_depth..=max_depth {
-            self.curr_depth = depth;
+        // the weights may have changed since the last search
+        self.eval_cache.clear();
+        self.params = EvalParams::active();
 
-            match self.alphabeta(board.clone(), -2000000, 2000000, depth) {
-                Ok(s) => score = s,
-                Err(o) => {
-                    output_best_move = o;
-                    break;
-                }
-            }
+        self.accs.clear();
+        self.root_black = board.black;
+        self.nnue = if self.use_nnue {
+            Network::active()
+        } else {
+            None
+        };
+
+        // a table hit at the root is played without searching
+        self.tbs = Tablebases::active();
+        let root_hit = self
+            .tbs
+            .as_ref()
+            .and_then(|tbs| tbs.best_move(&board, self.c960));
+
+        if let Some((mov, wdl)) = root_hit {
+            score = wdl.score() * 4;
+            best_move = Some(mov);
 
             if self.id == 0 {
-                print!(
-                    "info depth {} seldepth {} score {} pv ",
-                    depth,
-                    self.gens.len(),
-                    show_ibv(score)
-                );
-                self.show_pv(depth as usize, &board);
+                println!("info depth 1 score {} pv {}", show_ibv(score), mov);
+            }
 
-                if let Some(mov1) = self.get_best_move(&board) {
-                    best_move = Some(mov1);
+            // pondering and infinite searches still wait to be stopped
+            if max_depth == u8::MAX {
+                let time = self.stop_time.saturating_duration_since(Instant::now());
+                output_best_move = self.stop.recv_timeout(time).unwrap_or(true);
+            }
+        } else {
+            for depth in min_depth..=max_depth {
+                self.curr_depth = depth;
+
+                match self.alphabeta(board.clone(), -2000000, 2000000, depth) {
+                    Ok(s) => score = s,
+                    Err(o) => {
+                        output_best_move = o;
+                        break;
+                    }
+                }
 
-                    if let Some(mov2) = self.get_best_move(&board.do_move(mov1)) {
-                        ponder_move = Some(mov2);
+                if self.id == 0 {
+                    print!(
+                        "info depth {} seldepth {} score {} pv ",
+                        depth,
+                        self.gens.len(),
+                        show_ibv(score)
+                    );
+                    self.show_pv(depth as usize, &board);
+
+                    if let Some(mov1) = self.get_best_move(&board) {
+                        best_move = Some(mov1);
+
+                        if let Some(mov2) = self.get_best_move(&board.do_move(mov1)) {
+                            ponder_move = Some(mov2);
+                        }
                     }
                 }
-            }
 
-            if score.abs() >= CHECKMATE * 4 - 1 && self.get_best_move(&board).is_some() {
-                break;
+                if score.abs() >= CHECKMATE * 4 - 1 && self.get_best_move(&board).is_some() {
+                    break;
+                }
             }
         }
 
@@ -538,3 +742,25 @@ impl Searcher {
         score
     }
 }
+
+#[test]
+fn t_contempt() {
+    let mut searcher = Searcher::new_single(1 << 10, false);
+    let board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - -");
+
+    searcher.contempt = 20;
+    searcher.root_black = board.black;
+    searcher.curr_depth = 1;
+
+    // every move runs into the fifty-move rule
+    searcher.game = Game::with_clock(board.clone(), 99);
+    assert_eq!(
+        searcher.alphabeta(board.clone(), -2000000, 2000000, 1),
+        Ok(-80)
+    );
+
+    // and the opponent welcomes the draw
+    searcher.tt.clear();
+    searcher.root_black ^= true;
+    assert_eq!(searcher.alphabeta(board, -2000000, 2000000, 1), Ok(80));
+}
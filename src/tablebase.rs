@@ -0,0 +1,775 @@
+use crate::active::*;
+use crate::board::*;
+use crate::eval::*;
+use crate::gen_moves::*;
+use crate::gen_tables::*;
+use crate::moves::*;
+use crate::variant::*;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::simd::u64x4;
+use std::sync::Arc;
+
+/* table file format
+ *  "CTB" u8 version
+ *  u8 piece counts, queens to pawns, white then black
+ *  u32 number of positions, little endian
+ *  runs of (u8 length, u8 value) until all positions are covered
+ *
+ * a value of 0 is a draw, otherwise it is the distance to mate in plies
+ * plus one, the side to move winning when the distance is odd
+ */
+
+// the most pieces on the board, kings included, that tables are built for
+pub const MAX_MEN: usize = 4;
+// table wins are scored below real mates, shorter ones first
+pub const TB_WIN: i32 = CHECKMATE - 256;
+
+const VERSION: u8 = 1;
+
+// queens, rooks, bishops, knights and pawns
+const KINDS: [u8; 5] = [4, 6, 3, 2, 1];
+const KIND_NAMES: &str = "QRBNP";
+const KIND_VALUES: [u32; 5] = [9, 5, 3, 3, 1];
+
+lazy_static! {
+    // the squares the white king is moved to by symmetry, without and with
+    // pawns on the board
+    static ref KING_REGIONS: [Vec<usize>; 2] = [
+        (0..64).filter(|sq| sq % 8 <= sq / 8 && sq / 8 < 4).collect(),
+        (0..64).filter(|sq| sq % 8 < 4).collect(),
+    ];
+    static ref REGION_INDEX: [[usize; 64]; 2] = {
+        let mut out = [[0; 64]; 2];
+
+        for (pawns, region) in KING_REGIONS.iter().enumerate() {
+            for (i, sq) in region.iter().enumerate() {
+                out[pawns][*sq] = i;
+            }
+        }
+
+        out
+    };
+}
+
+// the bitplanes of `piece` standing on `sq`
+fn piece_bits(piece: u8, sq: usize) -> u64x4 {
+    u64x4::from_array([3, 2, 1, 0].map(|i| ((piece >> i & 1) as u64) << sq))
+}
+
+// bit 0 mirrors the files, bit 1 the ranks and bit 2 swaps files and ranks
+fn transform(mut sq: usize, sym: u8) -> usize {
+    if sym & 1 != 0 {
+        sq ^= 7;
+    }
+    if sym & 2 != 0 {
+        sq ^= 56;
+    }
+    if sym & 4 != 0 {
+        sq = sq % 8 * 8 + sq / 8;
+    }
+
+    sq
+}
+
+// the symmetry bringing the white king into its region, pawns only allow
+// mirroring the files
+fn symmetry(king: usize, pawns: bool) -> u8 {
+    if pawns {
+        return (king % 8 >= 4) as u8;
+    }
+
+    let sym = (king % 8 >= 4) as u8 | ((king / 8 >= 4) as u8) << 1;
+    let king = transform(king, sym);
+
+    sym | ((king % 8 > king / 8) as u8) << 2
+}
+
+/// Pieces besides the kings, counted per color and kind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Material([[u8; 5]; 2]);
+
+impl Material {
+    pub fn of(board: &Board) -> Self {
+        let mut out = [[0; 5]; 2];
+
+        for (color, counts) in out.iter_mut().enumerate() {
+            for (count, kind) in counts.iter_mut().zip(KINDS) {
+                let mut pieces = board.get_piece(kind | (color as u8) << 3);
+
+                // uncastled rooks
+                if kind == 6 {
+                    pieces |= board.get_piece(7 | (color as u8) << 3);
+                }
+
+                *count = pieces.count_ones() as u8;
+            }
+        }
+
+        Material(out)
+    }
+
+    pub fn men(&self) -> usize {
+        2 + self.0.iter().flatten().map(|n| *n as usize).sum::<usize>()
+    }
+
+    fn is_bare(&self) -> bool {
+        self.men() == 2
+    }
+
+    fn has_pawns(&self) -> bool {
+        self.0[0][4] + self.0[1][4] != 0
+    }
+
+    fn strength(counts: &[u8; 5]) -> (u32, [u8; 5]) {
+        let value = counts.iter().zip(KIND_VALUES).map(|(n, v)| *n as u32 * v);
+
+        (value.sum(), *counts)
+    }
+
+    /// The same material with the stronger side as white, and whether the
+    /// colors had to be swapped for that.
+    pub fn canonical(self) -> (Self, bool) {
+        if Material::strength(&self.0[1]) > Material::strength(&self.0[0]) {
+            (Material([self.0[1], self.0[0]]), true)
+        } else {
+            (self, false)
+        }
+    }
+
+    // everything a single capture or promotion can lead to
+    fn children(&self) -> Vec<Material> {
+        let mut out = Vec::new();
+
+        for color in 0..2 {
+            for kind in 0..5 {
+                if self.0[color][kind] == 0 {
+                    continue;
+                }
+
+                let mut captured = *self;
+                captured.0[color][kind] -= 1;
+                out.push(captured.canonical().0);
+
+                if kind == 4 {
+                    for promo in 0..4 {
+                        let mut promoted = captured;
+                        promoted.0[color][promo] += 1;
+                        out.push(promoted.canonical().0);
+                    }
+                }
+            }
+        }
+
+        out.sort();
+        out.dedup();
+        out
+    }
+
+    /// Every material with at most `max_men` pieces and a stronger or equal
+    /// white side, fewest pieces first.
+    pub fn all(max_men: usize) -> Vec<Material> {
+        let mut out = vec![Material([[0; 5]; 2])];
+        let mut i = 0;
+
+        while i < out.len() {
+            if out[i].men() < max_men {
+                for color in 0..2 {
+                    for kind in 0..5 {
+                        let mut next = out[i];
+                        next.0[color][kind] += 1;
+
+                        let next = next.canonical().0;
+                        if !out.contains(&next) {
+                            out.push(next);
+                        }
+                    }
+                }
+            }
+
+            i += 1;
+        }
+
+        out.remove(0);
+        out
+    }
+
+    /// Names like "KRKP", white's pieces first.
+    pub fn name(&self) -> String {
+        let mut out = String::new();
+
+        for counts in self.0.iter() {
+            out.push('K');
+
+            for (count, name) in counts.iter().zip(KIND_NAMES.chars()) {
+                out.extend(std::iter::repeat_n(name, *count as usize));
+            }
+        }
+
+        out
+    }
+
+    fn layout(&self) -> Layout {
+        let mut pieces = [5, 13, 0, 0];
+        let mut men = 2;
+
+        for color in 0..2 {
+            for (count, kind) in self.0[color].iter().zip(KINDS) {
+                for _ in 0..*count {
+                    pieces[men] = kind | (color as u8) << 3;
+                    men += 1;
+                }
+            }
+        }
+
+        let pawns = self.has_pawns();
+        let kings = KING_REGIONS[pawns as usize].len();
+
+        Layout {
+            pieces,
+            men,
+            pawns,
+            kings,
+            size: 2 * kings * 64usize.pow(men as u32 - 1),
+        }
+    }
+}
+
+/// How a table position ends with best play, as the distance to mate in
+/// plies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wdl {
+    Win(u8),
+    Draw,
+    Loss(u8),
+}
+
+impl Wdl {
+    fn from_value(value: u8) -> Self {
+        match value {
+            0 => Wdl::Draw,
+            v if v % 2 == 0 => Wdl::Win(v - 1),
+            v => Wdl::Loss(v - 1),
+        }
+    }
+
+    pub fn score(self) -> i32 {
+        match self {
+            Wdl::Win(plies) => TB_WIN - plies as i32,
+            Wdl::Draw => 0,
+            Wdl::Loss(plies) => plies as i32 - TB_WIN,
+        }
+    }
+}
+
+// positions are indexed by the side to move, the white king's square within
+// its region and the squares of the other pieces
+struct Layout {
+    pieces: [u8; MAX_MEN],
+    men: usize,
+    pawns: bool,
+    kings: usize,
+    size: usize,
+}
+
+impl Layout {
+    fn index(&self, board: &Board, flipped: bool) -> usize {
+        let mut squares = [0; MAX_MEN];
+        let mut taken = 0;
+
+        for (sq, piece) in squares.iter_mut().zip(&self.pieces[..self.men]) {
+            let piece = if flipped { piece ^ 8 } else { *piece };
+            let pieces = board.get_piece(piece) & !taken;
+
+            *sq = pieces.trailing_zeros() as usize;
+            taken |= 1 << *sq;
+
+            if flipped {
+                *sq ^= 56;
+            }
+        }
+
+        let sym = symmetry(squares[0], self.pawns);
+        let king = REGION_INDEX[self.pawns as usize][transform(squares[0], sym)];
+
+        squares[1..self.men].iter().fold(
+            (board.black ^ flipped) as usize * self.kings + king,
+            |idx, sq| idx * 64 + transform(*sq, sym),
+        )
+    }
+
+    // only legal positions are returned
+    fn board(&self, mut idx: usize) -> Option<Board> {
+        let mut b = u64x4::splat(0);
+        let mut occ = 0u64;
+        let mut placed = [0; MAX_MEN];
+
+        for sq in placed[1..self.men].iter_mut().rev() {
+            *sq = idx % 64;
+            idx /= 64;
+        }
+
+        placed[0] = KING_REGIONS[self.pawns as usize][idx % self.kings];
+        let black = idx / self.kings == 1;
+
+        for (sq, piece) in placed.iter().zip(&self.pieces[..self.men]) {
+            if occ & 1 << sq != 0 || *piece & 7 == 1 && (*sq < 8 || *sq >= 56) {
+                return None;
+            }
+
+            occ |= 1 << sq;
+            b |= piece_bits(*piece, *sq);
+        }
+
+        // positions are only ever looked up by index, so the hash is left
+        // unset
+        let board = Board {
+            b,
+            black,
+            ..Board::new()
+        };
+        let other = Board {
+            black: !black,
+            ..board.clone()
+        };
+
+        // the side that just moved can't be in check
+        if other.in_check() || TABLES.king[placed[0]] & 1 << placed[1] != 0 {
+            None
+        } else {
+            Some(board)
+        }
+    }
+}
+
+// every position `board` could have been reached from without a capture or
+// a promotion, some of which may be illegal
+fn unmoves(board: &Board) -> impl Iterator<Item = Board> + '_ {
+    let black = !board.black;
+    let occ = board.occ();
+    let own = if black { board.black() } else { board.white() };
+
+    LocStack(own).flat_map(move |sq| {
+        let piece = board.get_square(sq as u8);
+        let from = match piece & 7 {
+            1 => {
+                let (back, start) = if black {
+                    (sq + 8, 4)
+                } else {
+                    (sq.wrapping_sub(8), 3)
+                };
+                let mut out = 0;
+
+                if back < 64 && 1 << back & occ == 0 {
+                    out |= 1 << back;
+
+                    // double pushes
+                    if sq / 8 == start {
+                        let back2 = if black { sq + 16 } else { sq - 16 };
+                        out |= 1 << back2 & !occ;
+                    }
+                }
+
+                // the pawn can't have started on its first rank
+                out & 0x00ffffffffffff00
+            }
+            2 => TABLES.knight[sq],
+            3 => gen_bishop_moves(sq, occ),
+            4 => gen_bishop_moves(sq, occ) | gen_rook_moves(sq, occ),
+            5 => TABLES.king[sq],
+            _ => gen_rook_moves(sq, occ),
+        };
+
+        LocStack(from & !occ).map(move |from| Board {
+            b: board.b ^ piece_bits(piece, sq) ^ piece_bits(piece, from),
+            black,
+            ..board.clone()
+        })
+    })
+}
+
+pub struct Table {
+    material: Material,
+    values: Vec<u8>,
+}
+
+impl Table {
+    fn max_plies(&self) -> u8 {
+        self.values.iter().max().map_or(0, |v| v.saturating_sub(1))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = b"CTB".to_vec();
+
+        out.push(VERSION);
+        out.extend(self.material.0.iter().flatten());
+        out.extend((self.values.len() as u32).to_le_bytes());
+
+        for run in self.values.chunk_by(|a, b| a == b) {
+            for part in run.chunks(255) {
+                out.extend([part.len() as u8, part[0]]);
+            }
+        }
+
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ()> {
+        if bytes.len() < 18 || &bytes[..3] != b"CTB" || bytes[3] != VERSION {
+            return Err(());
+        }
+
+        let mut counts = [[0; 5]; 2];
+        counts[0].copy_from_slice(&bytes[4..9]);
+        counts[1].copy_from_slice(&bytes[9..14]);
+
+        let material = Material(counts);
+        let len = u32::from_le_bytes([bytes[14], bytes[15], bytes[16], bytes[17]]) as usize;
+
+        if material.men() > MAX_MEN || material.layout().size != len {
+            return Err(());
+        }
+
+        let mut values = Vec::with_capacity(len);
+
+        for run in bytes[18..].chunks(2) {
+            if let [n, value] = run {
+                values.extend(std::iter::repeat_n(*value, *n as usize));
+            } else {
+                return Err(());
+            }
+        }
+
+        if values.len() != len {
+            return Err(());
+        }
+
+        Ok(Table { material, values })
+    }
+}
+
+// set once tables have been loaded
+static LOADED_TABLES: Active<Tablebases> = Active::new();
+
+#[derive(Default)]
+pub struct Tablebases {
+    tables: HashMap<Material, Table>,
+}
+
+impl Tablebases {
+    /// The tables last passed to `set_active`, if any.
+    pub fn active() -> Option<Arc<Tablebases>> {
+        LOADED_TABLES.get()
+    }
+
+    pub fn set_active(tables: Tablebases) {
+        LOADED_TABLES.set(tables);
+    }
+
+    /// Reads every table file in `dir`.
+    pub fn load(dir: &str) -> Result<Self, ()> {
+        let mut out = Tablebases::default();
+
+        for entry in fs::read_dir(dir).map_err(|_| ())? {
+            let path = entry.map_err(|_| ())?.path();
+
+            if path.extension().is_some_and(|ext| ext == "ctb") {
+                let table = Table::from_bytes(&fs::read(&path).map_err(|_| ())?)?;
+                out.tables.insert(table.material, table);
+            }
+        }
+
+        Ok(out)
+    }
+
+    pub fn save(&self, dir: &str) -> Result<(), ()> {
+        for table in self.tables.values() {
+            let path = Path::new(dir).join(table.material.name() + ".ctb");
+
+            fs::write(path, table.to_bytes()).map_err(|_| ())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    /// Builds the table for `material`, and first those of everything it can
+    /// turn into.
+    pub fn generate(&mut self, material: Material) {
+        if material.is_bare() || self.tables.contains_key(&material) {
+            return;
+        }
+
+        for child in material.children() {
+            self.generate(child);
+        }
+
+        let layout = material.layout();
+        let mut values = vec![0; layout.size];
+        // legal positions that aren't decided yet, by their canonical index
+        let mut open = vec![false; layout.size];
+        let mut checked = vec![0; layout.size];
+        // positions decided through captures and promotions, by the pass that
+        // decides them
+        let mut scheduled = vec![Vec::new(); 256];
+        // en passant positions aren't in the table, so the positions leading
+        // to them are checked on every pass
+        let mut volatile = Vec::new();
+        let mut decided = Vec::new();
+        let mut generator = MoveGenerator::empty();
+
+        for idx in 0..layout.size {
+            let board = match layout.board(idx) {
+                Some(board) if layout.index(&board, false) == idx => board,
+                _ => continue,
+            };
+
+            generator.set_board(board.clone());
+            generator.gen_moves();
+
+            if generator.moves.is_empty() {
+                if board.in_check() {
+                    values[idx] = 1;
+                    decided.push(idx);
+                }
+                continue;
+            }
+
+            open[idx] = true;
+
+            if generator.moves.iter().any(|b| b.takeable_empties() != 0) {
+                volatile.push(idx);
+            }
+
+            let current = Some((material, &values[..]));
+            let children = generator.moves.iter();
+            let value = self.resolve(children.map(|b| self.value(b, current, u8::MAX)), u8::MAX);
+
+            if value != 0 {
+                scheduled[value as usize - 1].push(idx);
+            }
+        }
+
+        // retrograde passes, each one deciding the positions that are mate
+        // in `plies`
+        for plies in 1..255 {
+            let mut candidates = std::mem::take(&mut scheduled[plies as usize]);
+            candidates.extend(volatile.iter());
+
+            for idx in decided.drain(..) {
+                let board = layout.board(idx).unwrap();
+                candidates.extend(unmoves(&board).map(|b| layout.index(&b, false)));
+            }
+
+            for idx in candidates {
+                if !open[idx] || checked[idx] == plies {
+                    continue;
+                }
+                checked[idx] = plies;
+
+                generator.set_board(layout.board(idx).unwrap());
+                generator.gen_moves();
+
+                let current = Some((material, &values[..]));
+                let children = generator.moves.iter();
+                let value = self.resolve(children.map(|b| self.value(b, current, plies)), plies);
+
+                if value != 0 {
+                    values[idx] = value;
+                    open[idx] = false;
+                    decided.push(idx);
+                }
+            }
+
+            if decided.is_empty() && scheduled[plies as usize..].iter().all(Vec::is_empty) {
+                break;
+            }
+        }
+
+        self.tables.insert(material, Table { material, values });
+    }
+
+    // the value of a position from those of its children, if it is decided
+    // by the pass for `plies`
+    fn resolve(&self, children: impl Iterator<Item = u8>, plies: u8) -> u8 {
+        let mut win = None;
+        let mut loss = Some(0);
+
+        for value in children {
+            if value == 0 {
+                loss = None;
+            } else if value % 2 == 1 {
+                win = Some(win.map_or(value, |w: u8| w.min(value)));
+                loss = None;
+            } else {
+                loss = loss.map(|l: u8| l.max(value));
+            }
+        }
+
+        match (win, loss) {
+            (Some(w), _) if w <= plies => w + 1,
+            (None, Some(l)) if l <= plies => l + 1,
+            _ => 0,
+        }
+    }
+
+    // `current` is the table being built, which is complete up to `plies`
+    fn value(&self, board: &Board, current: Option<(Material, &[u8])>, plies: u8) -> u8 {
+        // en passant isn't part of the index, so look one move further
+        if board.takeable_empties() != 0 {
+            let mut generator = MoveGenerator::new(board.clone());
+            generator.gen_moves();
+
+            if generator.moves.is_empty() {
+                return board.in_check() as u8;
+            }
+
+            let children = generator.moves.iter();
+            let plies = plies.saturating_add(1);
+
+            return self.resolve(children.map(|b| self.value(b, current, plies)), plies);
+        }
+
+        let (material, flipped) = match current {
+            // without pawns, nothing but a capture changes the material
+            Some((m, _)) if !m.has_pawns() && board.occ().count_ones() as usize == m.men() => {
+                (m, false)
+            }
+            _ => Material::of(board).canonical(),
+        };
+
+        if material.is_bare() {
+            return 0;
+        }
+
+        let values = match current {
+            Some((m, values)) if m == material => values,
+            _ => match self.tables.get(&material) {
+                Some(table) => &table.values,
+                None => return 0,
+            },
+        };
+
+        values[material.layout().index(board, flipped)]
+    }
+
+    fn covers(&self, board: &Board) -> bool {
+        if board.variant != Variant::Standard
+            || board.occ().count_ones() as usize > MAX_MEN
+            || board.castling_rooks() != 0
+        {
+            return false;
+        }
+
+        let material = Material::of(board).canonical().0;
+
+        material.is_bare() || self.tables.contains_key(&material)
+    }
+
+    pub fn probe(&self, board: &Board) -> Option<Wdl> {
+        if self.covers(board) {
+            Some(Wdl::from_value(self.value(board, None, u8::MAX)))
+        } else {
+            None
+        }
+    }
+
+    /// The move keeping the best result: the fastest win, a draw, or else the
+    /// slowest loss.
+    pub fn best_move(&self, board: &Board, c960: bool) -> Option<(Move, Wdl)> {
+        if !self.covers(board) {
+            return None;
+        }
+
+        let mut generator = MoveGenerator::new(board.clone());
+        generator.gen_moves();
+
+        let best = generator
+            .moves
+            .iter()
+            .max_by_key(|b| -Wdl::from_value(self.value(b, None, u8::MAX)).score())?;
+
+        Some((board.get_move(best, c960), self.probe(board)?))
+    }
+}
+
+#[test]
+fn t_tablebase_kqk() {
+    let kqk = Material::of(&Board::from_fen("4k3/8/8/8/8/8/8/3QK3 w - -"));
+    let mut tbs = Tablebases::default();
+    tbs.generate(kqk);
+
+    assert_eq!(kqk.name(), "KQK");
+    assert_eq!(tbs.len(), 1);
+    // mate in ten from the worst square, one more ply for black to move
+    assert_eq!(tbs.tables[&kqk].max_plies(), 20);
+
+    let probe = |fen: &str| tbs.probe(&Board::from_fen(fen));
+
+    assert_eq!(probe("7k/8/6K1/8/8/8/Q7/8 w - -"), Some(Wdl::Win(1)));
+    assert_eq!(probe("7k/Q7/6K1/8/8/8/8/8 b - -"), Some(Wdl::Loss(2)));
+    // black to move is stalemated
+    assert_eq!(probe("k7/2Q5/1K6/8/8/8/8/8 b - -"), Some(Wdl::Draw));
+    // black takes the queen
+    assert_eq!(probe("8/8/8/8/8/8/1q6/K6k w - -"), Some(Wdl::Draw));
+    assert_eq!(probe("4k3/8/8/8/8/8/8/2RQK3 w - -"), None);
+
+    let board = Board::from_fen("7k/8/6K1/8/8/8/Q7/8 w - -");
+    assert_eq!(
+        tbs.best_move(&board, false),
+        Some(("a2a8".parse().unwrap(), Wdl::Win(1)))
+    );
+
+    let table = &tbs.tables[&kqk];
+    let table2 = Table::from_bytes(&table.to_bytes()).unwrap();
+
+    assert_eq!(table2.material, kqk);
+    assert!(table2.values == table.values);
+    assert!(Table::from_bytes(&table.to_bytes()[1..]).is_err());
+}
+
+#[test]
+fn t_tablebase_kpk() {
+    let kpk = Material::of(&Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - -"));
+    let mut tbs = Tablebases::default();
+    tbs.generate(kpk);
+
+    // with every table a promotion can lead to
+    assert_eq!(kpk.name(), "KPK");
+    assert_eq!(tbs.len(), 5);
+
+    let probe = |fen: &str| tbs.probe(&Board::from_fen(fen));
+
+    // the king in front of its pawn on the sixth rank wins either way
+    assert!(matches!(
+        probe("4k3/8/4K3/4P3/8/8/8/8 w - -"),
+        Some(Wdl::Win(_))
+    ));
+    assert!(matches!(
+        probe("4k3/8/4K3/4P3/8/8/8/8 b - -"),
+        Some(Wdl::Loss(_))
+    ));
+    // but pushing too early stalemates
+    assert_eq!(probe("4k3/4P3/4K3/8/8/8/8/8 b - -"), Some(Wdl::Draw));
+    // the king in front of a rook pawn holds
+    assert_eq!(probe("k7/8/8/8/8/8/P7/K7 w - -"), Some(Wdl::Draw));
+    // the pawn is taken
+    assert_eq!(probe("8/8/8/8/8/8/3kP3/7K b - -"), Some(Wdl::Draw));
+
+    // promoting to a queen mates fastest
+    let board = Board::from_fen("8/4P3/8/8/8/k7/8/4K3 w - -");
+    let (mov, wdl) = tbs.best_move(&board, false).unwrap();
+    assert_eq!(mov, "e7e8q".parse().unwrap());
+    assert!(matches!(wdl, Wdl::Win(_)));
+
+    // an en passant square nobody can take on changes nothing
+    assert_eq!(
+        probe("4k3/8/8/8/4P3/8/8/4K3 b - e3"),
+        probe("4k3/8/8/8/4P3/8/8/4K3 b - -")
+    );
+}
@@ -6,7 +6,6 @@ use crate::board::*;
 use crate::eval::*;
 use crate::gen_moves::*;
 use crate::search::*;
-use crate::tt::*;
 
 use std::fs::File;
 use std::io::{self, BufRead, BufReader};
@@ -27,65 +26,166 @@ fn read_positions(file: &str) -> Vec<(f64, Board)> {
     out
 }
 
-use std::convert::TryInto;
+// the tuned weights, in the order they're laid out in the vector
+const WEIGHTS: usize = 35;
+const PST_PIECES: [usize; 12] = [1, 2, 3, 4, 5, 6, 9, 10, 11, 12, 13, 14];
 
 impl EvalParams {
+    // every weight takes two entries, middlegame then endgame, and every
+    // piece-square table 128, all the middlegame values then the endgame ones
     fn from_vec(vec: &[i32]) -> Self {
-        EvalParams {
-            chain_weight: vec[0],
-            passed_weight: vec[1],
-            doubled_weight: vec[2],
-            isolated_weight: vec[3],
-            king_pawn_weight: vec[4],
-
-            castle_bonus: vec[5],
-
-            knight_move_weight: vec[6],
-            bishop_move_weight: vec[7],
-            rook_move_weight: vec[8],
-            queen_move_weight: vec[9],
-            king_move_weight: vec[10],
-
-            pawn_weight: 100,
-            knight_weight: 279,
-            bishop_weight: 293,
-            rook_weight: 466,
-            queen_weight: 866,
-            king_weight: 25600,
+        let w = |i: usize| Score(vec[2 * i], vec[2 * i + 1]);
+
+        let mut out = EvalParams {
+            chain_weight: w(0),
+            doubled_weight: w(1),
+            isolated_weight: w(2),
+            backward_weight: w(3),
+            island_weight: w(4),
+            candidate_weight: w(5),
+            connected_passer_weight: w(6),
+            phalanx_weight: w(7),
+            pawn_center_weight: w(8),
+
+            castle_bonus: w(9),
+
+            knight_move_weight: w(10),
+            bishop_move_weight: w(11),
+            rook_move_weight: w(12),
+            queen_move_weight: w(13),
+            king_move_weight: w(14),
+
+            bishop_pair_weight: w(15),
+            rook_open_weight: w(16),
+            rook_semi_open_weight: w(17),
+            rook_seventh_weight: w(18),
+            knight_outpost_weight: w(19),
+            bishop_outpost_weight: w(20),
+            bad_bishop_weight: w(21),
+            trapped_bishop_weight: w(22),
+            trapped_rook_weight: w(23),
+
+            hanging_weight: w(24),
+            pawn_threat_weight: w(25),
+            minor_threat_weight: w(26),
+            rook_threat_weight: w(27),
+            pawn_push_threat_weight: w(28),
+            weak_queen_weight: w(29),
+
+            passer_support_weight: w(30),
+            passer_own_king_weight: w(31),
+            passer_enemy_king_weight: w(32),
+            rook_behind_passer_weight: w(33),
+            unstoppable_passer_weight: w(34),
 
             psts: {
-                let mut psts = [[0; 64]; 16];
+                let mut psts = [[Score(0, 0); 64]; 16];
 
-                for (i, j) in [1, 2, 3, 4, 5, 6, 9, 10, 11, 12, 13, 14].iter().enumerate() {
-                    let start = i * 64 + 11;
+                for (i, j) in PST_PIECES.iter().enumerate() {
+                    let start = 2 * WEIGHTS + i * 128;
 
-                    psts[*j] = vec[start..start + 64].try_into().unwrap();
+                    for sq in 0..64 {
+                        psts[*j][sq] = Score(vec[start + sq], vec[start + 64 + sq]);
+                    }
                 }
 
                 psts
             },
+
+            ..EvalParams::default()
+        };
+
+        let mut i = WEIGHTS + PST_PIECES.len() * 64;
+
+        for score in out.tables_mut().iter_mut().flat_map(|s| s.iter_mut()) {
+            *score = w(i);
+            i += 1;
         }
+
+        out
+    }
+
+    // the other tables of weights, which come after the piece-square tables
+    fn tables_mut(&mut self) -> [&mut [Score]; 9] {
+        [
+            &mut self.imbalance_ours,
+            &mut self.imbalance_theirs,
+            &mut self.passed_weights,
+            &mut self.free_passer_weights,
+            &mut self.king_attack_weights,
+            &mut self.safe_check_weights,
+            &mut self.king_safety_table,
+            &mut self.shelter_weights,
+            &mut self.storm_weights,
+        ]
+    }
+
+    fn tables(&self) -> [&[Score]; 9] {
+        [
+            &self.imbalance_ours,
+            &self.imbalance_theirs,
+            &self.passed_weights,
+            &self.free_passer_weights,
+            &self.king_attack_weights,
+            &self.safe_check_weights,
+            &self.king_safety_table,
+            &self.shelter_weights,
+            &self.storm_weights,
+        ]
     }
 
     fn to_vec(&self) -> Vec<i32> {
         let mut out = Vec::new();
 
-        out.push(self.chain_weight);
-        out.push(self.passed_weight);
-        out.push(self.doubled_weight);
-        out.push(self.isolated_weight);
-        out.push(self.king_pawn_weight);
-
-        out.push(self.castle_bonus);
+        for w in [
+            self.chain_weight,
+            self.doubled_weight,
+            self.isolated_weight,
+            self.backward_weight,
+            self.island_weight,
+            self.candidate_weight,
+            self.connected_passer_weight,
+            self.phalanx_weight,
+            self.pawn_center_weight,
+            self.castle_bonus,
+            self.knight_move_weight,
+            self.bishop_move_weight,
+            self.rook_move_weight,
+            self.queen_move_weight,
+            self.king_move_weight,
+            self.bishop_pair_weight,
+            self.rook_open_weight,
+            self.rook_semi_open_weight,
+            self.rook_seventh_weight,
+            self.knight_outpost_weight,
+            self.bishop_outpost_weight,
+            self.bad_bishop_weight,
+            self.trapped_bishop_weight,
+            self.trapped_rook_weight,
+            self.hanging_weight,
+            self.pawn_threat_weight,
+            self.minor_threat_weight,
+            self.rook_threat_weight,
+            self.pawn_push_threat_weight,
+            self.weak_queen_weight,
+            self.passer_support_weight,
+            self.passer_own_king_weight,
+            self.passer_enemy_king_weight,
+            self.rook_behind_passer_weight,
+            self.unstoppable_passer_weight,
+        ] {
+            out.push(w.0);
+            out.push(w.1);
+        }
 
-        out.push(self.knight_move_weight);
-        out.push(self.bishop_move_weight);
-        out.push(self.rook_move_weight);
-        out.push(self.queen_move_weight);
-        out.push(self.king_move_weight);
+        for i in PST_PIECES {
+            out.extend(self.psts[i].iter().map(|s| s.0));
+            out.extend(self.psts[i].iter().map(|s| s.1));
+        }
 
-        for i in [1, 2, 3, 4, 5, 6, 9, 10, 11, 12, 13, 14] {
-            out.extend(self.psts[i]);
+        for score in self.tables().iter().flat_map(|s| s.iter()) {
+            out.push(score.0);
+            out.push(score.1);
         }
 
         out
@@ -299,13 +399,13 @@ fn precompute(file: &str, positions: &[(f64, Board)], params: &EvalParams) -> Ve
     let mut params = params.to_vec();
 
     let mut generator = MoveGenerator::empty();
-    let mut pawn_tt = TT::with_len(16384);
+    let mut pawn_tt = PawnTable::with_len(16384);
     let mut evals = Vec::new();
 
     let mut params2 = EvalParams::from_vec(&params);
 
     for (_, board) in positions {
-        evals.push(generator.eval_with_params(board.clone(), &mut pawn_tt, &params2));
+        evals.push(generator.eval_with_params(board.clone(), &mut pawn_tt, &params2, None, 0));
     }
 
     let mut out = Vec::new();
@@ -320,7 +420,7 @@ fn precompute(file: &str, positions: &[(f64, Board)], params: &EvalParams) -> Ve
         pawn_tt.clear();
 
         for (j, (_, board)) in positions.iter().enumerate() {
-            let eval = generator.eval_with_params(board.clone(), &mut pawn_tt, &params2);
+            let eval = generator.eval_with_params(board.clone(), &mut pawn_tt, &params2, None, 0);
 
             vec.push(eval != evals[j]);
 
@@ -353,7 +453,7 @@ struct ParamTuner {
     best_error: f64,
 
     generator: MoveGenerator,
-    pawn_tt: TT,
+    pawn_tt: PawnTable,
 }
 
 fn win_prob(eval: i32) -> f64 {
@@ -372,9 +472,9 @@ impl ParamTuner {
         let mut total_error = 0.;
 
         for (i, (expected, board)) in self.positions.iter().enumerate() {
-            let eval = self
-                .generator
-                .eval_with_params(board.clone(), &mut self.pawn_tt, &params);
+            let eval =
+                self.generator
+                    .eval_with_params(board.clone(), &mut self.pawn_tt, &params, None, 0);
             let error = eval_error(*expected, eval);
 
             self.errors1[i] = error;
@@ -403,7 +503,7 @@ impl ParamTuner {
             best_error: 0.,
 
             generator: MoveGenerator::empty(),
-            pawn_tt: TT::with_len(1024),
+            pawn_tt: PawnTable::with_len(1024),
         };
 
         out.init_errors();
@@ -424,9 +524,9 @@ impl ParamTuner {
 
             if *affects {
                 let board = self.positions[i].1.clone();
-                let eval = self
-                    .generator
-                    .eval_with_params(board, &mut self.pawn_tt, &params);
+                let eval =
+                    self.generator
+                        .eval_with_params(board, &mut self.pawn_tt, &params, None, 0);
 
                 error = eval_error(self.positions[i].0, eval);
             } else {
@@ -87,9 +87,9 @@ use std::fs::File;
 use std::io::Write;
 
 use crate::board::*;
+use crate::eval::*;
 use crate::gen_moves::*;
 use crate::search::*;
-use crate::tt::*;
 
 pub fn positions_from_games(file1: &str, file2: &str) {
     let read = File::open(file1).unwrap();
@@ -106,7 +106,7 @@ pub fn positions_from_games(file1: &str, file2: &str) {
         .map(|p| p.unwrap())
         .flatten();
 
-    let mut pawn_tt = TT::with_len(1024);
+    let mut pawn_tt = PawnTable::with_len(1024);
     let mut searcher = Searcher::new_single(0, false);
     let mut generator = MoveGenerator::empty();
 
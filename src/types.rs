@@ -0,0 +1,482 @@
+use crate::board::*;
+
+use std::fmt;
+use std::ops::Not;
+use std::str::FromStr;
+
+// Typed wrappers around the raw square indices, piece codes and side-to-move
+// flag used internally by Board. Squares keep the internal layout, where
+// square 0 is h1 and square 63 is a8.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct File(u8);
+
+impl File {
+    pub const fn new(index: u8) -> Option<Self> {
+        if index < 8 {
+            Some(File(index))
+        } else {
+            None
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Self> {
+        FILES.find(c.to_ascii_lowercase()).map(|i| File(i as u8))
+    }
+
+    pub fn to_char(self) -> char {
+        FILES.as_bytes()[self.0 as usize] as char
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn all() -> impl Iterator<Item = File> {
+        (0..8).map(File)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Rank(u8);
+
+impl Rank {
+    pub const fn new(index: u8) -> Option<Self> {
+        if index < 8 {
+            Some(Rank(index))
+        } else {
+            None
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            '1'..='8' => Some(Rank(c as u8 - b'1')),
+            _ => None,
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        (b'1' + self.0) as char
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// Rank counted from the given side's first rank.
+    pub fn relative_to(self, color: Color) -> Rank {
+        match color {
+            Color::White => self,
+            Color::Black => Rank(7 - self.0),
+        }
+    }
+
+    pub fn all() -> impl Iterator<Item = Rank> {
+        (0..8).map(Rank)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Square(u8);
+
+impl Square {
+    pub const fn new(index: usize) -> Option<Self> {
+        if index < 64 {
+            Some(Square(index as u8))
+        } else {
+            None
+        }
+    }
+
+    pub const fn from_coords(file: File, rank: Rank) -> Self {
+        Square(file.0 + rank.0 * 8)
+    }
+
+    pub fn file(self) -> File {
+        File(self.0 % 8)
+    }
+
+    pub fn rank(self) -> Rank {
+        Rank(self.0 / 8)
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn bit(self) -> u64 {
+        1 << self.0
+    }
+
+    pub fn all() -> impl Iterator<Item = Square> {
+        (0..64).map(Square)
+    }
+}
+
+impl FromStr for Square {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+
+        let file = chars.next().and_then(File::from_char).ok_or(())?;
+        let rank = chars.next().and_then(Rank::from_char).ok_or(())?;
+
+        if chars.next().is_some() {
+            return Err(());
+        }
+
+        Ok(Square::from_coords(file, rank))
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.file().to_char(), self.rank().to_char())
+    }
+}
+
+/// Iterates over the squares of a bitboard, lowest first.
+pub struct Squares(pub u64);
+
+impl Iterator for Squares {
+    type Item = Square;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0 == 0 {
+            None
+        } else {
+            let out = self.0.trailing_zeros();
+            self.0 &= self.0 - 1;
+            Some(Square(out as u8))
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Color {
+    White,
+    Black,
+}
+
+impl Color {
+    pub fn from_black(black: bool) -> Self {
+        if black {
+            Color::Black
+        } else {
+            Color::White
+        }
+    }
+
+    pub fn is_black(self) -> bool {
+        self == Color::Black
+    }
+}
+
+impl Not for Color {
+    type Output = Color;
+
+    fn not(self) -> Color {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Color::White => write!(f, "w"),
+            Color::Black => write!(f, "b"),
+        }
+    }
+}
+
+// discriminants match the low three bits of the square encoding in board.rs
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PieceKind {
+    Pawn = 1,
+    Knight = 2,
+    Bishop = 3,
+    Queen = 4,
+    King = 5,
+    Rook = 6,
+}
+
+impl PieceKind {
+    pub const ALL: [PieceKind; 6] = [
+        PieceKind::Pawn,
+        PieceKind::Knight,
+        PieceKind::Bishop,
+        PieceKind::Rook,
+        PieceKind::Queen,
+        PieceKind::King,
+    ];
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code & 7 {
+            1 => Some(PieceKind::Pawn),
+            2 => Some(PieceKind::Knight),
+            3 => Some(PieceKind::Bishop),
+            4 => Some(PieceKind::Queen),
+            5 => Some(PieceKind::King),
+            6 | 7 => Some(PieceKind::Rook),
+            _ => None,
+        }
+    }
+
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_char(c: char) -> Option<Self> {
+        FEN_PIECES[9..15]
+            .find(c.to_ascii_lowercase())
+            .and_then(|i| Self::from_code(i as u8 + 1))
+    }
+
+    pub fn to_char(self) -> char {
+        FEN_PIECES.as_bytes()[self as usize + 8] as char
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Piece {
+    pub color: Color,
+    pub kind: PieceKind,
+}
+
+impl Piece {
+    pub fn new(color: Color, kind: PieceKind) -> Self {
+        Self { color, kind }
+    }
+
+    /// Decodes a square code from board.rs. Empty and en-passant squares have
+    /// no piece, and uncastled rooks decode as plain rooks.
+    pub fn from_code(code: u8) -> Option<Self> {
+        PieceKind::from_code(code).map(|kind| Self {
+            color: Color::from_black(code & 8 != 0),
+            kind,
+        })
+    }
+
+    pub fn code(self) -> u8 {
+        self.kind.code() | (self.color.is_black() as u8) << 3
+    }
+
+    pub fn from_char(c: char) -> Option<Self> {
+        PieceKind::from_char(c).map(|kind| Self {
+            color: Color::from_black(c.is_ascii_lowercase()),
+            kind,
+        })
+    }
+
+    pub fn to_char(self) -> char {
+        match self.color {
+            Color::White => self.kind.to_char().to_ascii_uppercase(),
+            Color::Black => self.kind.to_char(),
+        }
+    }
+}
+
+impl fmt::Display for Piece {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+impl FromStr for Piece {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Piece::from_char(c).ok_or(()),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The castling rights of a position, decoded from the uncastled rook bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CastlingRights {
+    rooks: [u64; 2],
+    kings: [Option<Square>; 2],
+}
+
+impl CastlingRights {
+    pub fn is_empty(&self) -> bool {
+        self.rooks == [0, 0]
+    }
+
+    /// Squares of the rooks that the given side may still castle with.
+    pub fn rooks(&self, color: Color) -> Squares {
+        Squares(self.rooks[color as usize])
+    }
+
+    pub fn has(&self, color: Color, rook: Square) -> bool {
+        self.rooks[color as usize] & rook.bit() != 0
+    }
+
+    pub fn kingside(&self, color: Color) -> Option<Square> {
+        let king = self.kings[color as usize]?;
+
+        self.rooks(color).find(|r| r.file() < king.file())
+    }
+
+    pub fn queenside(&self, color: Color) -> Option<Square> {
+        let king = self.kings[color as usize]?;
+
+        self.rooks(color).filter(|r| r.file() > king.file()).last()
+    }
+
+    /// Formats the rights as the castling field of a FEN string. Chess960
+    /// rights use file letters, otherwise only corner rooks are written.
+    pub fn fen(&self, c960: bool) -> String {
+        let mut castles = Vec::new();
+
+        for color in [Color::White, Color::Black] {
+            for sq in self.rooks(color) {
+                let c = if c960 {
+                    sq.file().to_char()
+                } else if sq.file().index() == 0 {
+                    'k'
+                } else if sq.file().index() == 7 {
+                    'q'
+                } else {
+                    continue;
+                };
+
+                castles.push(if color == Color::White {
+                    c.to_ascii_uppercase()
+                } else {
+                    c
+                });
+            }
+        }
+
+        if castles.is_empty() {
+            "-".to_string()
+        } else {
+            castles.sort();
+            castles.into_iter().collect()
+        }
+    }
+}
+
+impl fmt::Display for CastlingRights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.fen(false))
+    }
+}
+
+impl Board {
+    pub fn side_to_move(&self) -> Color {
+        Color::from_black(self.black)
+    }
+
+    pub fn piece_at(&self, sq: Square) -> Option<Piece> {
+        Piece::from_code(self.get_square(sq.0))
+    }
+
+    /// Bitboard of all pieces of the given type and color.
+    pub fn pieces_of(&self, piece: Piece) -> u64 {
+        let occ = match piece.color {
+            Color::White => self.white(),
+            Color::Black => self.black(),
+        };
+
+        let kinds = match piece.kind {
+            PieceKind::Pawn => self.pawns(),
+            PieceKind::Knight => self.knights(),
+            PieceKind::Bishop => self.bishops(),
+            PieceKind::Rook => self.rooks(),
+            PieceKind::Queen => self.queens(),
+            PieceKind::King => self.kings(),
+        };
+
+        kinds & occ
+    }
+
+    pub fn pieces(&self) -> impl Iterator<Item = (Square, Piece)> + '_ {
+        Squares(self.occ()).filter_map(move |sq| Some((sq, self.piece_at(sq)?)))
+    }
+
+    pub fn king_square(&self, color: Color) -> Option<Square> {
+        Squares(self.pieces_of(Piece::new(color, PieceKind::King))).next()
+    }
+
+    pub fn ep_square(&self) -> Option<Square> {
+        Squares(self.takeable_empties()).next()
+    }
+
+    pub fn castling_rights(&self) -> CastlingRights {
+        CastlingRights {
+            rooks: [self.castling_white_rooks(), self.castling_black_rooks()],
+            kings: [
+                self.king_square(Color::White),
+                self.king_square(Color::Black),
+            ],
+        }
+    }
+}
+
+#[test]
+fn t_square() {
+    let e4: Square = "e4".parse().unwrap();
+
+    assert_eq!(e4.index(), sq_from_str("e4"));
+    assert_eq!(e4.to_string(), "e4");
+    assert_eq!(e4.file().to_char(), 'e');
+    assert_eq!(e4.rank().to_char(), '4');
+    assert_eq!(e4.rank().relative_to(Color::Black).to_char(), '5');
+
+    for s in ["", "e", "e9", "i1", "e44", "4e"] {
+        assert!(s.parse::<Square>().is_err());
+    }
+
+    assert!(Square::all().all(|sq| sq.to_string().parse() == Ok(sq)));
+}
+
+#[test]
+fn t_piece() {
+    for (i, c) in FEN_PIECES.chars().enumerate() {
+        let piece = Piece::from_code(i as u8);
+
+        if i % 8 == 0 {
+            assert_eq!(piece, None);
+        } else {
+            assert_eq!(piece.unwrap().to_char(), c);
+            assert_eq!(c.to_string().parse(), Ok(piece.unwrap()));
+        }
+    }
+
+    assert_eq!(Piece::from_code(15).unwrap().code(), 14);
+    assert!("x".parse::<Piece>().is_err());
+}
+
+#[test]
+fn t_board_pieces() {
+    let board = Board::from_fen("4k3/8/8/8/4P3/8/8/R3K2R b KQ e3");
+
+    let mut pieces = board
+        .pieces()
+        .map(|(sq, p)| format!("{}{}", p, sq))
+        .collect::<Vec<_>>();
+    pieces.sort();
+
+    assert_eq!(pieces, vec!["Ke1", "Pe4", "Ra1", "Rh1", "ke8"]);
+    assert_eq!(board.side_to_move(), Color::Black);
+    assert_eq!(board.ep_square(), "e3".parse().ok());
+    assert_eq!(board.king_square(Color::Black), "e8".parse().ok());
+
+    let rights = board.castling_rights();
+
+    assert_eq!(rights.kingside(Color::White), "h1".parse().ok());
+    assert_eq!(rights.queenside(Color::White), "a1".parse().ok());
+    assert_eq!(rights.kingside(Color::Black), None);
+    assert_eq!(rights.to_string(), "KQ");
+}
@@ -12,9 +12,11 @@ use super::*;
 
 #[derive(Clone, Debug)]
 pub enum SearcherCommand {
-    SetBoard(Board, Vec<Move>),
+    SetGame(Box<Game>),
     SetDebug(bool),
     SetC960(bool),
+    SetNNUE(bool),
+    SetContempt(i32),
     Search(Duration, u8),
     SearchPerft(usize, Arc<Mutex<Vec<Move>>>, Arc<AtomicU64>),
     Exit,
@@ -24,9 +26,6 @@ pub use SearcherCommand::*;
 
 impl Searcher {
     pub fn listen(&mut self) {
-        let mut board = Board::from_fen(START_FEN);
-        let mut moves = Vec::new();
-
         while let Ok(msg) = self.recv.recv() {
             match msg {
                 SearchPerft(depth, moves, total) => {
@@ -44,7 +43,7 @@ impl Searcher {
                         if let Some(mov) = lock.pop() {
                             std::mem::drop(lock);
 
-                            let board2 = board.do_move(mov);
+                            let board2 = self.game.board().do_move(mov);
                             let res = self.perft(board2, depth - 1);
                             total.fetch_add(res, Ordering::Relaxed);
 
@@ -60,25 +59,17 @@ impl Searcher {
                 }
                 Search(time, d) => {
                     self.stop_time = Instant::now() + time;
-                    self.prev_pos.clear();
-
-                    let mut board2 = board.clone();
-
-                    for m in &moves {
-                        self.incr_prev_pos(board2.hash);
-                        board2 = board2.do_move(*m);
-                    }
-
                     self.history = [[[0usize; 64]; 64]; 2];
 
-                    self.search(board2.clone(), d.min(1), d);
+                    self.search(self.game.board().clone(), d.min(1), d);
                 }
-                SetBoard(b, m) => {
-                    board = b;
-                    moves = m;
+                SetGame(g) => {
+                    self.game = *g;
                     self.incr_time();
                 }
                 SetC960(b) => self.c960 = b,
+                SetNNUE(b) => self.use_nnue = b,
+                SetContempt(c) => self.contempt = c,
                 Exit => break,
                 _ => {}
             }
@@ -91,18 +82,16 @@ struct ThreadPool {
     sends: Vec<Sender<SearcherCommand>>,
     stops: Vec<Sender<bool>>,
     tt: TT,
-    pawn_tt: TT,
     tasks: Vec<usize>,
 }
 
 impl ThreadPool {
-    fn new(tt: TT, pawn_tt: TT, len: usize) -> Self {
+    fn new(tt: TT, len: usize) -> Self {
         let mut out = ThreadPool {
             threads: Vec::new(),
             sends: Vec::new(),
             stops: Vec::new(),
             tt,
-            pawn_tt,
             tasks: Vec::new(),
         };
 
@@ -120,11 +109,10 @@ impl ThreadPool {
             self.stops.push(s_st);
 
             let tt = self.tt.clone();
-            let pawn_tt = self.pawn_tt.clone();
             let id = self.threads.len();
 
             self.threads.push(thread::spawn(move || {
-                let mut searcher = Searcher::new(tt, pawn_tt, recv, r_st, id);
+                let mut searcher = Searcher::new(tt, recv, r_st, id);
 
                 searcher.listen();
             }));
@@ -198,19 +186,22 @@ where
     T: Read,
 {
     let mut tt = TT::with_len(62500);
-    let pawn_tt = TT::with_len(62500);
 
     let mut generator = MoveGenerator::empty();
-    let searcher = Searcher::new(tt.clone(), pawn_tt.clone(), channel().1, channel().1, 0);
-    let mut threads = ThreadPool::new(tt.clone(), pawn_tt.clone(), 1);
+    let searcher = Searcher::new(tt.clone(), channel().1, channel().1, 0);
+    let mut threads = ThreadPool::new(tt.clone(), 1);
 
     let mut lines = read.lines();
     let mut line = lines.next().unwrap().unwrap();
     let mut words = line.split_whitespace();
 
     let mut c960 = false;
-    let mut board = Board::from_fen(START_FEN);
-    let mut moves = Vec::new();
+    let mut use_nnue = false;
+    let (mut contempt, mut rating_adv) = (0, 0);
+    // the side to move in the last search, whose draw scores are in the table
+    let mut draw_side = None;
+    let mut variant = Standard;
+    let mut game = Game::from_fen(START_FEN);
 
     'outer: loop {
         match words.next() {
@@ -223,66 +214,124 @@ where
                 println!("option name Threads type spin default 1 min 1 max 64");
                 println!("option name Ponder type check default false");
                 println!("option name UCI_Chess960 type check default false");
+                println!("option name EvalFile type string default <empty>");
+                println!("option name UseNNUE type check default false");
+                println!("option name NNUEFile type string default <empty>");
+                println!("option name TablebasePath type string default <empty>");
+                println!("option name Contempt type spin default 0 min -100 max 100");
+                println!("option name UCI_RatingAdv type spin default 0 min -3000 max 3000");
+
+                let vars = Variant::ALL
+                    .iter()
+                    .map(|v| format!(" var {}", v.uci_name()));
+                println!(
+                    "option name UCI_Variant type combo default {}{}",
+                    Standard.uci_name(),
+                    vars.collect::<String>()
+                );
                 println!("uciok");
             }
             Some("position") => {
                 let pos = words.next().unwrap();
+                let mut rest = words.by_ref().peekable();
 
-                if pos == "startpos" {
-                    board = Board::from_fen(START_FEN);
-                } else {
-                    let mut s = String::new();
+                // an index is optional, so only take the next word if it is one
+                let mut index = || {
+                    let i = rest.peek()?.parse::<u16>().ok()?;
+                    rest.next();
 
-                    for _ in 0..6 {
-                        s += words.next().unwrap();
-                        s += " ";
+                    if i < 960 {
+                        Some(i)
+                    } else {
+                        println!("info string invalid position index {}", i);
+                        None
                     }
+                };
 
-                    board = Board::from_fen(&s);
+                if pos == "startpos" {
+                    game = Game::from_fen(START_FEN);
+                } else if pos == "chess960" {
+                    game = Game::new(Board::chess960(index().unwrap_or(518)));
+                } else if pos == "dfrc" {
+                    let white = index().unwrap_or(518);
+                    let black = index().unwrap_or(white);
+
+                    game = Game::new(Board::dfrc(white, black));
+                } else {
+                    // variant FENs may have extra fields, so read up to "moves"
+                    let s = rest
+                        .by_ref()
+                        .take_while(|w| *w != "moves")
+                        .collect::<Vec<_>>();
+
+                    game = Game::from_fen(&s.join(" "));
                 }
 
-                let _ = words.next();
+                game = game.with_variant(variant);
+
+                if pos != "fen" {
+                    rest.next_if_eq(&"moves");
+                }
 
-                moves = words.map(Move::from_uci).collect::<Vec<_>>();
+                for mov in rest.map(Move::from_uci) {
+                    game.push(mov);
+                }
 
                 line = lines.next().unwrap().unwrap();
                 words = line.split_whitespace();
 
-                threads.send_all(SetBoard(board.clone(), moves.clone()));
+                threads.send_all(SetGame(Box::new(game.clone())));
             }
             Some("getposition") => {
-                let board2 = moves.iter().fold(board.clone(), |b, m| b.do_move(*m));
+                println!(
+                    "info string {} {} {}",
+                    game.board().to_fen(c960),
+                    game.halfmove_clock(),
+                    game.fullmove_number()
+                );
+            }
+            Some("eval") => {
+                let board = game.board().clone();
+
+                print!("{}", generator.eval_trace(board.clone()));
+
+                if let (true, Some(net)) = (use_nnue, Network::active()) {
+                    let acc = Accumulator::new(&net, &board);
 
-                println!("info string {} 0 0", board2.to_fen(c960));
+                    println!(
+                        "NNUE: {} (side to move)",
+                        generator.eval_nnue(board, &net, &acc, 0)
+                    );
+                }
             }
             Some("domoves") => {
-                moves.extend(words.map(Move::from_uci));
+                for mov in words.map(Move::from_uci) {
+                    game.push(mov);
+                }
 
                 line = lines.next().unwrap().unwrap();
                 words = line.split_whitespace();
 
-                threads.send_all(SetBoard(board.clone(), moves.clone()));
+                threads.send_all(SetGame(Box::new(game.clone())));
             }
             Some("dobestmove") => {
                 let reps = words.next().unwrap_or("1").parse::<usize>().unwrap_or(1);
 
-                let board2 = moves.iter().fold(board.clone(), |b, m| b.do_move(*m));
-
                 for _ in 0..reps {
-                    if let Some(mov) = searcher.get_best_move(&board2) {
-                        moves.push(mov);
+                    if let Some(mov) = searcher.get_best_move(game.board()) {
+                        game.push(mov);
                     } else {
                         break;
                     }
                 }
 
-                threads.send_all(SetBoard(board.clone(), moves.clone()));
+                threads.send_all(SetGame(Box::new(game.clone())));
             }
             Some("waitonsearch") => {
                 threads.send_all(Exit);
                 threads.join();
 
-                threads = ThreadPool::new(tt.clone(), pawn_tt.clone(), 1);
+                threads = ThreadPool::new(tt.clone(), 1);
             }
             Some("setoption") => {
                 let mut val = false;
@@ -320,6 +369,55 @@ where
                             c960 = c;
                         }
                     }
+                    "EvalFile" => match value.trim() {
+                        "" | "<empty>" => {}
+                        path => match EvalParams::load(path) {
+                            // the pawn hash tables notice the new weights
+                            Ok(params) => EvalParams::set_active(params),
+                            Err(()) => println!("info string could not load {}", path),
+                        },
+                    },
+                    "UseNNUE" => {
+                        if let Ok(b) = value.trim().parse::<bool>() {
+                            threads.send_all(SetNNUE(b));
+                            use_nnue = b;
+                        }
+                    }
+                    "NNUEFile" => match value.trim() {
+                        "" | "<empty>" => {}
+                        path => match Network::load(path) {
+                            Ok(net) => Network::set_active(net),
+                            Err(()) => println!("info string could not load {}", path),
+                        },
+                    },
+                    "TablebasePath" => match value.trim() {
+                        "" | "<empty>" => {}
+                        path => match Tablebases::load(path) {
+                            Ok(tbs) => Tablebases::set_active(tbs),
+                            Err(()) => println!("info string could not load {}", path),
+                        },
+                    },
+                    "Contempt" | "UCI_RatingAdv" => {
+                        if let Ok(n) = value.trim().parse::<i32>() {
+                            if name.trim() == "Contempt" {
+                                contempt = n;
+                            } else {
+                                rating_adv = n;
+                            }
+
+                            // draws are worse against weaker opponents
+                            let c = (contempt + rating_adv / 20).clamp(-100, 100);
+                            threads.send_all(SetContempt(c));
+                            draw_side = None;
+                        }
+                    }
+                    "UCI_Variant" => {
+                        if let Some(v) = Variant::from_uci(value.trim()) {
+                            variant = v;
+                            game = game.with_variant(variant);
+                            threads.send_all(SetGame(Box::new(game.clone())));
+                        }
+                    }
                     _ => {}
                 }
                 line = lines.next().unwrap().unwrap();
@@ -351,7 +449,7 @@ where
                             }
                         }
                         "wtime" | "btime" => {
-                            if board.black ^ (moves.len() & 1 != 0) == (w == "btime") {
+                            if game.board().black == (w == "btime") {
                                 if let Some(w) = words.next() {
                                     if let Ok(t) = w.parse::<u64>() {
                                         time = Duration::from_millis(t);
@@ -360,7 +458,7 @@ where
                             }
                         }
                         "winc" | "binc" => {
-                            if board.black ^ (moves.len() & 1 != 0) == (w == "binc") {
+                            if game.board().black == (w == "binc") {
                                 if let Some(w) = words.next() {
                                     if let Ok(t) = w.parse::<u64>() {
                                         inc = Duration::from_millis(t);
@@ -386,6 +484,8 @@ where
                                 continue;
                             };
 
+                            let board = game.board();
+
                             generator.set_board(board.clone());
                             generator.gen_moves();
 
@@ -414,6 +514,15 @@ where
                     }
                 }
 
+                // stored scores count draws for or against the old side
+                if contempt != 0 || rating_adv != 0 {
+                    let black = game.board().black;
+
+                    if draw_side.replace(black) != Some(black) {
+                        tt.clear();
+                    }
+                }
+
                 if ponder {
                     threads.stop_all(true);
                     threads.send_all(Search(Duration::from_secs(3155760000), 255));
@@ -457,9 +566,8 @@ where
 
 pub fn perftmanager(ttsize: usize, threads: usize, board: Board, depth: usize) {
     let tt = TT::with_len(ttsize);
-    let pawn_tt = TT::with_len(1);
 
-    let mut threads = ThreadPool::new(tt.clone(), pawn_tt.clone(), threads);
+    let mut threads = ThreadPool::new(tt.clone(), threads);
 
     let mut generator = MoveGenerator::new(board.clone());
     generator.gen_moves();
@@ -473,7 +581,7 @@ pub fn perftmanager(ttsize: usize, threads: usize, board: Board, depth: usize) {
     let moves = Arc::new(Mutex::new(moves));
     let total = Arc::new(AtomicU64::new(0));
 
-    threads.send_all(SetBoard(board.clone(), Vec::new()));
+    threads.send_all(SetGame(Box::new(Game::new(board.clone()))));
     threads.send_all(SearchPerft(depth, moves.clone(), total.clone()));
     threads.send_all(Exit);
 
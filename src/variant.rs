@@ -0,0 +1,399 @@
+use crate::board::*;
+use crate::gen_tables::*;
+use crate::types::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum Variant {
+    #[default]
+    Standard,
+    ThreeCheck,
+    KingOfTheHill,
+    Antichess,
+    Crazyhouse,
+    Atomic,
+}
+
+pub use Variant::*;
+
+impl Variant {
+    pub const ALL: [Variant; 6] = [
+        Standard,
+        ThreeCheck,
+        KingOfTheHill,
+        Antichess,
+        Crazyhouse,
+        Atomic,
+    ];
+
+    /// Name used by the UCI_Variant option.
+    pub fn uci_name(self) -> &'static str {
+        match self {
+            Standard => "chess",
+            ThreeCheck => "3check",
+            KingOfTheHill => "kingofthehill",
+            Antichess => "antichess",
+            Crazyhouse => "crazyhouse",
+            Atomic => "atomic",
+        }
+    }
+
+    pub fn from_uci(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|v| v.uci_name().eq_ignore_ascii_case(name))
+    }
+}
+
+// d4, e4, d5 and e5
+pub const HILL: u64 = 0x0000001818000000;
+
+/// Parses a three-check FEN counter of the checks each side still has to
+/// give, either as written by `Board::to_fen` (`+3+3`) or without the
+/// leading plus (`3+3`).
+pub fn parse_checks(word: &str) -> Option<[u8; 2]> {
+    let mut nums = word.strip_prefix('+').unwrap_or(word).split('+');
+
+    let white = nums.next()?.parse::<u8>().ok()?;
+    let black = nums.next()?.parse::<u8>().ok()?;
+
+    if nums.next().is_some() || white > 3 || black > 3 {
+        None
+    } else {
+        Some([white, black])
+    }
+}
+
+// order in which pocket pieces are written to a FEN
+const POCKET_PIECES: [u8; 5] = [4, 6, 3, 2, 1];
+
+/// Parses the pieces in hand of a crazyhouse FEN, such as `Qn` in
+/// `[Qn]`.
+pub fn parse_pockets(s: &str) -> [[u8; 8]; 2] {
+    let mut out = [[0; 8]; 2];
+
+    for piece in s.chars().filter_map(Piece::from_char) {
+        if piece.kind != PieceKind::King {
+            out[piece.color.is_black() as usize][piece.kind.code() as usize] += 1;
+        }
+    }
+
+    out
+}
+
+impl Board {
+    pub fn pocket_fen(&self) -> String {
+        let mut out = String::new();
+
+        for black in [false, true] {
+            for code in POCKET_PIECES {
+                let piece = Piece::from_code(code | (black as u8) << 3).unwrap();
+
+                for _ in 0..self.pockets[black as usize][code as usize] {
+                    out.push(piece.to_char());
+                }
+            }
+        }
+
+        out
+    }
+
+    pub fn with_variant(mut self, variant: Variant) -> Self {
+        self.variant = variant;
+
+        // there is no castling in antichess
+        if variant == Antichess {
+            self.b ^= u64x4::from_array([0, 0, 0, self.castling_rooks()]);
+        }
+
+        self.init_hash();
+        self
+    }
+
+    /// Applies the variant-specific side effects of the move from `prev`,
+    /// for both `do_move` and positions built by the move generator.
+    pub fn update_variant(&mut self, prev: &Board) {
+        match self.variant {
+            Standard | KingOfTheHill | Antichess => {}
+            Crazyhouse => {
+                let before = self.clone();
+                let color = prev.black as usize;
+
+                let (prev_cur, prev_opp, cur, opp) = if prev.black {
+                    (prev.black(), prev.white(), self.black(), self.white())
+                } else {
+                    (prev.white(), prev.black(), self.white(), self.black())
+                };
+
+                let captured = prev_opp & !opp;
+                let from = prev_cur & !cur;
+                let to = cur & !prev_cur;
+
+                for sq in LocStack(captured) {
+                    let code = if prev.promoted & 1 << sq != 0 {
+                        1
+                    } else {
+                        // uncastled rooks are put in the pocket as rooks
+                        (prev.get_square(sq as u8) & 7).min(6)
+                    };
+
+                    self.pockets[color][code as usize] += 1;
+                }
+
+                // a drop is the only move that adds a piece, while a castle
+                // swapping the king and rook can leave `from` empty too
+                if prev.occ().count_ones() < self.occ().count_ones() && to != 0 {
+                    let code = self.get_square(to.trailing_zeros() as u8) & 7;
+
+                    self.pockets[color][code as usize] -= 1;
+                }
+
+                self.promoted &= !captured & !from;
+
+                if from.count_ones() == 1
+                    && (prev.promoted & from != 0
+                        || prev.pawns() & from != 0 && self.pawns() & to == 0)
+                {
+                    self.promoted |= to;
+                }
+
+                self.update_hash(&before);
+            }
+            ThreeCheck => {
+                if self.in_check() {
+                    let before = self.clone();
+
+                    self.checks[prev.black as usize] =
+                        self.checks[prev.black as usize].saturating_sub(1);
+                    self.update_hash(&before);
+                }
+            }
+            Atomic => {
+                let (prev_cur, prev_opp, cur, opp) = if prev.black {
+                    (prev.black(), prev.white(), self.black(), self.white())
+                } else {
+                    (prev.white(), prev.black(), self.white(), self.black())
+                };
+
+                // the capturing piece and every non-pawn next to it explode
+                if prev_opp & !opp != 0 {
+                    let before = self.clone();
+                    let center = (cur & !prev_cur).trailing_zeros() as usize;
+                    let blast = TABLES.king[center] & !self.pawns() | 1 << center;
+
+                    self.b &= u64x4::splat(!blast);
+
+                    // an exploded king takes its castling rights with it
+                    let mut rooks = 0;
+
+                    if self.kings() & self.white() == 0 {
+                        rooks |= self.castling_white_rooks();
+                    }
+                    if self.kings() & self.black() == 0 {
+                        rooks |= self.castling_black_rooks();
+                    }
+
+                    self.b ^= u64x4::from_array([0, 0, 0, rooks]);
+                    self.update_hash(&before);
+                }
+            }
+        }
+    }
+
+    /// Whether the side to move has already lost by a variant rule.
+    pub fn is_variant_loss(&self) -> bool {
+        let opp_occ = if self.black {
+            self.white()
+        } else {
+            self.black()
+        };
+
+        match self.variant {
+            Standard | Antichess | Crazyhouse => false,
+            Atomic => self.kings() & !opp_occ == 0,
+            ThreeCheck => self.checks[!self.black as usize] == 0,
+            KingOfTheHill => self.kings() & opp_occ & HILL != 0,
+        }
+    }
+}
+
+#[test]
+fn t_parse_checks() {
+    assert_eq!(parse_checks("3+3"), Some([3, 3]));
+    assert_eq!(parse_checks("1+2"), Some([1, 2]));
+    assert_eq!(parse_checks("+1+0"), Some([1, 0]));
+    assert_eq!(parse_checks("++1+0"), None);
+    assert_eq!(parse_checks("4+3"), None);
+    assert_eq!(parse_checks("0"), None);
+    assert_eq!(parse_checks("-"), None);
+}
+
+#[test]
+fn t_three_check() {
+    use crate::gen_moves::*;
+
+    let fen = "rnbqkbnr/ppp2ppp/8/3pp3/4P3/5Q2/PPPP1PPP/RNB1KBNR w KQkq - 2+3 0 3";
+    let board = Board::from_fen(fen).with_variant(ThreeCheck);
+
+    assert_eq!(board.checks, [2, 3]);
+    assert_eq!(
+        board.to_fen(false),
+        "rnbqkbnr/ppp2ppp/8/3pp3/4P3/5Q2/PPPP1PPP/RNB1KBNR w KQkq - +2+3"
+    );
+    assert_eq!(
+        board,
+        Board::from_fen(&board.to_fen(false)).with_variant(ThreeCheck)
+    );
+
+    let board2 = board.do_move("f1b5".parse().unwrap());
+    let mut board3 = board2.clone();
+    board3.init_hash();
+
+    assert_eq!(board2.checks, [1, 3]);
+    assert_eq!(board2.hash, board3.hash);
+    assert!(!board2.is_variant_loss());
+
+    let mut generator = MoveGenerator::new(board.clone());
+    generator.gen_moves();
+
+    assert!(generator.moves.contains(&board2));
+
+    let board = board.do_move("e4d5".parse().unwrap());
+    assert_eq!(board.checks, [2, 3]);
+
+    let board = Board::from_fen("4k3/8/8/8/8/8/8/4KQ2 w - - 1+3").with_variant(ThreeCheck);
+    let board = board.do_move("f1f7".parse().unwrap());
+
+    assert!(board.is_variant_loss());
+}
+
+#[test]
+fn t_king_of_the_hill() {
+    let board = Board::from_fen("4k3/8/8/8/8/4K3/8/8 w - -").with_variant(KingOfTheHill);
+
+    assert!(!board.is_variant_loss());
+    assert!(board.do_move("e3e4".parse().unwrap()).is_variant_loss());
+    assert!(!board.do_move("e3f3".parse().unwrap()).is_variant_loss());
+}
+
+#[test]
+fn t_crazyhouse() {
+    use crate::gen_moves::*;
+    use crate::moves::*;
+
+    let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R[] w KQkq -";
+    let board = Board::from_fen(fen).with_variant(Crazyhouse);
+
+    assert_eq!(board.to_fen(false), fen);
+
+    // captures go into the capturer's pocket
+    let board = board.do_move("f3e5".parse().unwrap());
+    let board = board.do_move("c6e5".parse().unwrap());
+
+    assert_eq!(board.pockets[0][1], 1);
+    assert_eq!(board.pockets[1][2], 1);
+    assert_eq!(board.pocket_fen(), "Pn");
+
+    // drops take pieces out of the pocket
+    let mov: Move = "N@d5".parse().unwrap();
+    assert_eq!(mov.to_string(), "N@d5");
+    assert_eq!(mov.dropped(), Some(PieceKind::Knight));
+
+    let board = board.do_move("P@d6".parse().unwrap());
+    let mut board2 = board.clone();
+    board2.init_hash();
+
+    assert_eq!(board.pockets[0][1], 0);
+    assert_eq!(board.hash, board2.hash);
+
+    let mut generator = MoveGenerator::new(board.clone());
+    generator.gen_moves();
+
+    let drop = board.do_move(mov.to_string().to_lowercase().parse().unwrap());
+    assert!(generator.moves.contains(&drop));
+    assert_eq!(board.get_move(&drop, false), "n@d5".parse().unwrap());
+
+    // captured promoted pieces return as pawns
+    let fen = "4k2Q~/8/8/8/8/8/8/4K3[] b - -";
+    let board = Board::from_fen(fen).with_variant(Crazyhouse);
+
+    assert_eq!(board.to_fen(false), fen);
+
+    let board = Board::from_fen("4k2r/6P1/8/8/8/8/8/4K3[] w - -").with_variant(Crazyhouse);
+    let board = board.do_move("g7h8q".parse().unwrap());
+
+    assert_eq!(board.promoted, 1 << 56);
+
+    let board = board.do_move("e8f7".parse().unwrap());
+    let board = board.do_move("h8h7".parse().unwrap());
+    let board = board.do_move("f7f6".parse().unwrap());
+
+    assert_eq!(board.promoted, 1 << 48);
+
+    let board = Board::from_fen("4k3/6Q~/8/8/8/8/8/4K3[] b - -").with_variant(Crazyhouse);
+    let board = board.do_move("e8f7".parse().unwrap());
+    let board = board.do_move("e1e2".parse().unwrap());
+    let board = board.do_move("f7g7".parse().unwrap());
+
+    assert_eq!(board.pocket_fen(), "p");
+    assert_eq!(board.promoted, 0);
+}
+
+#[test]
+fn t_crazyhouse_castling() {
+    use crate::gen_moves::*;
+
+    // the king and rook swap squares, adding no piece
+    let board = Board::from_fen("4k3/8/8/8/8/8/8/5KR1[N] w G -").with_variant(Crazyhouse);
+    let next = board.do_move("f1g1".parse().unwrap());
+    assert_eq!(next.pockets, board.pockets);
+    assert_eq!(next.to_fen(true), "4k3/8/8/8/8/8/8/5RK1[N] b - -");
+
+    let mut board2 = next.clone();
+    board2.init_hash();
+    assert_eq!(next.hash, board2.hash);
+
+    let mut generator = MoveGenerator::new(board);
+    generator.gen_moves();
+    assert!(generator.moves.contains(&next));
+}
+
+#[test]
+fn t_crazyhouse_drops() {
+    use crate::gen_moves::*;
+
+    // no pawn drops on the first and last rank
+    let board = Board::from_fen("7k/8/8/8/8/8/8/K7[Pp] w - -").with_variant(Crazyhouse);
+    let mut generator = MoveGenerator::new(board);
+    generator.gen_moves();
+
+    assert_eq!(generator.moves.len(), 3 + 48);
+
+    // drops can block checks
+    let board = Board::from_fen("r6k/8/8/8/8/8/8/K7[N] w - -").with_variant(Crazyhouse);
+    let mut generator = MoveGenerator::new(board);
+    generator.gen_moves();
+
+    assert_eq!(generator.moves.len(), 2 + 6);
+
+    let board = Board::from_fen("r6k/8/8/8/8/8/1r6/K7[N] w - -").with_variant(Crazyhouse);
+    let mut generator = MoveGenerator::new(board);
+
+    assert!(generator.has_moves());
+
+    let board = Board::from_fen("r6k/8/8/8/8/8/rr6/K7[] w - -").with_variant(Crazyhouse);
+    let mut generator = MoveGenerator::new(board);
+
+    assert!(!generator.has_moves());
+
+    // dropping on the en passant square
+    let board = Board::from_fen("4k3/8/8/6p1/8/8/8/4K3[Q] w - g6").with_variant(Crazyhouse);
+    let next = board.do_move("Q@g6".parse().unwrap());
+
+    assert_eq!(next.to_fen(false), "4k3/8/6Q1/6p1/8/8/8/4K3[] b - -");
+
+    let mut generator = MoveGenerator::new(board);
+    generator.gen_moves();
+
+    assert!(generator.moves.contains(&next));
+}